@@ -0,0 +1,471 @@
+//! Host driven LED animations.
+//!
+//! The firmware only runs its own [`LEDMode`] effects, so an [`Animation`] renders frames on the
+//! host and pushes them as [`LEDMode::Custom`] key colors, one report per frame. The pad needs
+//! [`crate::REPORT_DELAY`] between reports, which caps animations at 20 frames per second.
+
+use std::time::{Duration, Instant};
+
+use crate::{
+    Color, Falcon8, Flow, InputEvent, Key, KeyPress, LEDMode, Mode, Report, Result, Transport,
+};
+
+/// Colors of every key, indexed by [`Key`].
+pub type Frame = [Color; 8];
+
+/// Something that renders frames, like the built in [`Rainbow`], [`Ripple`], [`Gradient`] and
+/// [`ProgressBar`] effects.
+pub trait Effect {
+    /// Renders the frame shown `elapsed` into the animation.
+    fn render(&mut self, elapsed: Duration) -> Frame;
+
+    /// Called when `key` is pressed `elapsed` into the animation. Does nothing by default.
+    fn key_pressed(&mut self, _key: Key, _elapsed: Duration) {}
+}
+
+impl<E: Effect + ?Sized> Effect for Box<E> {
+    fn render(&mut self, elapsed: Duration) -> Frame {
+        (**self).render(elapsed)
+    }
+
+    fn key_pressed(&mut self, key: Key, elapsed: Duration) {
+        (**self).key_pressed(key, elapsed)
+    }
+}
+
+/// Where an [`Animation`] takes the time from.
+pub trait Clock {
+    /// Time since the clock started.
+    fn now(&self) -> Duration;
+
+    fn sleep(&mut self, duration: Duration);
+}
+
+/// The wall clock, starting when it is created.
+#[derive(Debug, Copy, Clone)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+/// A clock that only moves when slept on or advanced, so animations render the same frames on
+/// every run.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ManualClock {
+    now: Duration,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&mut self, duration: Duration) {
+        self.now += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.now
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+/// [`Key::position`] as floats, for effects to do geometry with.
+fn grid(key: Key) -> (f32, f32) {
+    let (row, column) = key.position();
+    (row as f32, column as f32)
+}
+
+/// Position of a key along `flow` from 0 to 1, or `None` for [`Flow::Constant`].
+fn along(key: Key, flow: Flow) -> Option<f32> {
+    let (row, column) = grid(key);
+    match flow {
+        Flow::LeftToRight => Some(column / (Key::COLUMNS - 1) as f32),
+        Flow::RightToLeft => Some(1.0 - column / (Key::COLUMNS - 1) as f32),
+        Flow::TopToBottom => Some(row / (Key::ROWS - 1) as f32),
+        // clockwise around the edge of the grid, starting from the top left
+        Flow::Spinning => {
+            let step = if row == 0.0 {
+                column
+            } else {
+                (Key::ALL.len() - 1) as f32 - column
+            };
+            Some(step / Key::ALL.len() as f32)
+        }
+        Flow::Constant => None,
+    }
+}
+
+fn mix(from: Color, to: Color, t: f32) -> Color {
+    let t = t.clamp(0.0, 1.0);
+    let channel = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * t).round() as u8;
+    Color::new(
+        channel(from.red, to.red),
+        channel(from.green, to.green),
+        channel(from.blue, to.blue),
+    )
+}
+
+/// The whole hue circle sweeping across the keys.
+#[derive(Debug, Clone)]
+pub struct Rainbow {
+    /// Time for a key to go around the hue circle once
+    pub period: Duration,
+    /// Direction of the sweep, [`Flow::Constant`] turning every key the same color
+    pub flow: Flow,
+    /// How much of the hue circle is spread over the keys at once, from 0 to 1
+    pub spread: f32,
+    pub saturation: f32,
+    pub value: f32,
+}
+
+/// Rings of color spreading from every pressed key, fading as they go.
+#[derive(Debug, Clone)]
+pub struct Ripple {
+    pub color: Color,
+    /// Keys a ring travels per second
+    pub speed: f32,
+    /// Time for a ring to fade out
+    pub fade: Duration,
+    /// Color of the keys no ring is passing over
+    pub background: Color,
+    presses: Vec<(Key, Duration)>,
+}
+
+/// A still gradient across the keys.
+#[derive(Debug, Clone)]
+pub struct Gradient {
+    pub from: Color,
+    pub to: Color,
+    /// Direction of the gradient, [`Flow::Spinning`] going around the edge of the grid
+    pub flow: Flow,
+}
+
+/// The keys filling up in order from [`Key::One`] to [`Key::Eight`] as progress is made.
+#[derive(Debug, Clone)]
+pub struct ProgressBar {
+    pub fill: Color,
+    pub empty: Color,
+    progress: f32,
+}
+
+impl Rainbow {
+    pub fn new(period: Duration) -> Self {
+        Self {
+            period,
+            flow: Flow::LeftToRight,
+            spread: 1.0,
+            saturation: 1.0,
+            value: 1.0,
+        }
+    }
+
+    pub fn flow(mut self, flow: Flow) -> Self {
+        self.flow = flow;
+        self
+    }
+
+    pub fn spread(mut self, spread: f32) -> Self {
+        self.spread = spread;
+        self
+    }
+}
+
+impl Effect for Rainbow {
+    fn render(&mut self, elapsed: Duration) -> Frame {
+        let turn = if self.period.is_zero() {
+            0.0
+        } else {
+            elapsed.as_secs_f32() / self.period.as_secs_f32()
+        };
+
+        Key::ALL.map(|key| {
+            let offset = along(key, self.flow).unwrap_or(0.0) * self.spread;
+            Color::from_hsv((turn - offset) * 360.0, self.saturation, self.value)
+        })
+    }
+}
+
+impl Ripple {
+    pub fn new(color: impl Into<Color>) -> Self {
+        Self {
+            color: color.into(),
+            speed: 8.0,
+            fade: Duration::from_millis(600),
+            background: Color::BLACK,
+            presses: Vec::new(),
+        }
+    }
+
+    pub fn speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn fade(mut self, fade: Duration) -> Self {
+        self.fade = fade;
+        self
+    }
+
+    pub fn background(mut self, background: impl Into<Color>) -> Self {
+        self.background = background.into();
+        self
+    }
+}
+
+impl Effect for Ripple {
+    fn render(&mut self, elapsed: Duration) -> Frame {
+        self.presses
+            .retain(|(_, pressed)| elapsed.saturating_sub(*pressed) < self.fade);
+
+        Key::ALL.map(|key| {
+            let (row, column) = grid(key);
+
+            let intensity = self
+                .presses
+                .iter()
+                .map(|(origin, pressed)| {
+                    let age = elapsed.saturating_sub(*pressed).as_secs_f32();
+                    let (origin_row, origin_column) = grid(*origin);
+                    let distance = (row - origin_row).hypot(column - origin_column);
+
+                    // a ring one key wide, dimming over its lifetime
+                    let ring = 1.0 - (distance - age * self.speed).abs();
+                    ring.max(0.0) * (1.0 - age / self.fade.as_secs_f32())
+                })
+                .fold(0.0, f32::max);
+
+            mix(self.background, self.color, intensity)
+        })
+    }
+
+    fn key_pressed(&mut self, key: Key, elapsed: Duration) {
+        self.presses.push((key, elapsed));
+    }
+}
+
+impl Gradient {
+    pub fn new(from: impl Into<Color>, to: impl Into<Color>) -> Self {
+        Self {
+            from: from.into(),
+            to: to.into(),
+            flow: Flow::LeftToRight,
+        }
+    }
+
+    pub fn flow(mut self, flow: Flow) -> Self {
+        self.flow = flow;
+        self
+    }
+}
+
+impl Effect for Gradient {
+    fn render(&mut self, _elapsed: Duration) -> Frame {
+        Key::ALL.map(|key| mix(self.from, self.to, along(key, self.flow).unwrap_or(0.0)))
+    }
+}
+
+impl ProgressBar {
+    pub fn new(fill: impl Into<Color>, empty: impl Into<Color>) -> Self {
+        Self {
+            fill: fill.into(),
+            empty: empty.into(),
+            progress: 0.0,
+        }
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+
+    /// Sets the progress from 0 to 1, the key it ends on being partly filled.
+    pub fn set_progress(&mut self, progress: f32) {
+        self.progress = progress.clamp(0.0, 1.0);
+    }
+}
+
+impl Effect for ProgressBar {
+    fn render(&mut self, _elapsed: Duration) -> Frame {
+        let filled = self.progress * Key::ALL.len() as f32;
+        Key::ALL.map(|key| mix(self.empty, self.fill, filled - key as usize as f32))
+    }
+}
+
+/// Plays an [`Effect`] on a pad, as [`LEDMode::Custom`] key colors.
+///
+/// The current key report is read on the first frame, and every frame after that is a single
+/// [`Mode::KeyWrite`] of it with new colors. Nothing is finalized, so the pad goes back to its
+/// saved lighting when unplugged.
+pub struct Animation<E: Effect, C: Clock = SystemClock> {
+    effect: E,
+    clock: C,
+    frame_interval: Duration,
+    last_frame: Option<Duration>,
+    report: Option<Report>,
+}
+
+impl<E: Effect> Animation<E> {
+    pub fn new(effect: E) -> Self {
+        Self::with_clock(effect, SystemClock::new())
+    }
+}
+
+impl<E: Effect, C: Clock> Animation<E, C> {
+    /// An animation timed by `clock`, at 20 frames per second.
+    pub fn with_clock(effect: E, clock: C) -> Self {
+        Self {
+            effect,
+            clock,
+            frame_interval: Duration::from_millis(50),
+            last_frame: None,
+            report: None,
+        }
+    }
+
+    /// Sets the frame rate. Rates the transport can't keep up with are capped to one frame per
+    /// [`Transport::report_delay`].
+    pub fn frame_rate(mut self, frames_per_second: u32) -> Self {
+        self.frame_interval = Duration::from_secs(1) / frames_per_second.max(1);
+        self
+    }
+
+    pub fn effect(&self) -> &E {
+        &self.effect
+    }
+
+    /// The effect being played, e.g. to move a [`ProgressBar`] along.
+    pub fn effect_mut(&mut self) -> &mut E {
+        &mut self.effect
+    }
+
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    pub fn clock_mut(&mut self) -> &mut C {
+        &mut self.clock
+    }
+
+    /// Time between two frames on `falcon8`.
+    pub fn frame_interval<T: Transport>(&self, falcon8: &Falcon8<T>) -> Duration {
+        self.frame_interval.max(falcon8.transport.report_delay())
+    }
+
+    /// Tells the effect `key` was pressed.
+    pub fn key_pressed(&mut self, key: Key) {
+        let now = self.clock.now();
+        self.effect.key_pressed(key, now);
+    }
+
+    /// Passes on the presses of an [`InputEvent`] that came from a pad key.
+    pub fn handle_event(&mut self, event: &InputEvent) {
+        match *event {
+            InputEvent::Key {
+                key: Some(key),
+                press: KeyPress::Down,
+                ..
+            }
+            | InputEvent::Consumer {
+                key: Some(key),
+                press: KeyPress::Down,
+                ..
+            }
+            | InputEvent::MouseButton {
+                key: Some(key),
+                press: KeyPress::Down,
+                ..
+            } => self.key_pressed(key),
+            _ => {}
+        }
+    }
+
+    /// Renders the frame for the current time without sending it.
+    pub fn render(&mut self) -> Frame {
+        let now = self.clock.now();
+        self.effect.render(now)
+    }
+
+    /// Waits until the next frame is due, then renders and sends it.
+    pub fn step<T: Transport>(&mut self, falcon8: &mut Falcon8<T>) -> Result<Frame> {
+        if let Some(last_frame) = self.last_frame {
+            let due = last_frame + self.frame_interval(falcon8);
+            let now = self.clock.now();
+            if now < due {
+                self.clock.sleep(due - now);
+            }
+        }
+
+        let mut report = match self.report {
+            Some(report) => report,
+            None => {
+                let mut report = falcon8.get_keys()?;
+                report.set_mode(Mode::KeyWrite).clear_end();
+                report.set_led_mode(LEDMode::Custom);
+                falcon8.led_controls.set_mode(LEDMode::Custom);
+                report
+            }
+        };
+
+        self.last_frame = Some(self.clock.now());
+        let frame = self.render();
+        for key in Key::ALL {
+            report.set_key_color(key, frame[key as usize]);
+            falcon8.led_controls.set_key_color(key, frame[key as usize]);
+        }
+
+        falcon8.set_report(&report)?;
+        self.report = Some(report);
+
+        Ok(frame)
+    }
+
+    /// Plays frames until `duration` has passed on the clock.
+    pub fn run_for<T: Transport>(
+        &mut self,
+        falcon8: &mut Falcon8<T>,
+        duration: Duration,
+    ) -> Result<()> {
+        let end = self.clock.now() + duration;
+        loop {
+            let due = match self.last_frame {
+                Some(last_frame) => last_frame + self.frame_interval(falcon8),
+                None => self.clock.now(),
+            };
+            if due >= end {
+                break;
+            }
+
+            self.step(falcon8)?;
+        }
+
+        Ok(())
+    }
+}
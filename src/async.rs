@@ -0,0 +1,221 @@
+use std::sync::Arc;
+
+use rusb::{Context, UsbContext};
+use tokio::{
+    sync::{mpsc, Mutex, MutexGuard, OwnedMutexGuard},
+    task::JoinError,
+};
+
+use crate::{Falcon8, InputEvent, Layer, Report, Result, Transport, UsbTransport};
+
+/// A [`Falcon8`] for tokio services.
+///
+/// USB transfers run on tokio's blocking thread pool, and the pauses the pad needs between
+/// reports are awaited with [`tokio::time::sleep`] instead of putting a thread to sleep. Every
+/// operation holds the lock from its first report to its last, so neither other updates nor the
+/// reads of [`AsyncFalcon8::events`] can interleave with its reports.
+pub struct AsyncFalcon8<T: Transport + Send + 'static = UsbTransport<Context>> {
+    inner: Arc<Mutex<Falcon8<T>>>,
+}
+
+/// The pad's [`InputEvent`]s, read on a thread of their own. See [`AsyncFalcon8::events`].
+pub struct EventStream {
+    events: mpsc::UnboundedReceiver<Result<InputEvent>>,
+}
+
+/// The [`Falcon8`] locked for one operation, handed to the blocking thread pool for each transfer.
+struct Session<T: Transport + Send + 'static> {
+    falcon8: Option<OwnedMutexGuard<Falcon8<T>>>,
+}
+
+/// The result of a blocking task, passing on its panic if it had one.
+fn join<R>(result: std::result::Result<R, JoinError>) -> R {
+    result.unwrap_or_else(|err| match err.try_into_panic() {
+        Ok(panic) => std::panic::resume_unwind(panic),
+        // blocking tasks are only cancelled when the runtime shuts down
+        Err(err) => panic!("{err}"),
+    })
+}
+
+impl<T: Transport + Send + 'static> Session<T> {
+    fn falcon8(&mut self) -> &mut Falcon8<T> {
+        self.falcon8
+            .as_mut()
+            .expect("the lock is only away during a transfer")
+    }
+
+    /// Runs `f` on the blocking thread pool, keeping the lock.
+    async fn blocking<R: Send + 'static>(
+        &mut self,
+        f: impl FnOnce(&mut Falcon8<T>) -> Result<R> + Send + 'static,
+    ) -> Result<R> {
+        let mut falcon8 = self
+            .falcon8
+            .take()
+            .expect("the lock is only away during a transfer");
+
+        let (falcon8, result) = join(
+            tokio::task::spawn_blocking(move || {
+                let result = f(&mut falcon8);
+                (falcon8, result)
+            })
+            .await,
+        );
+        self.falcon8 = Some(falcon8);
+
+        result
+    }
+
+    async fn pause(&mut self) {
+        let delay = self.falcon8().transport.report_delay();
+        tokio::time::sleep(delay).await;
+    }
+
+    async fn set_report(&mut self, report: Report) -> Result<()> {
+        self.blocking(move |falcon8| falcon8.send_report(&report))
+            .await?;
+        self.pause().await;
+
+        Ok(())
+    }
+
+    async fn get_report(&mut self, mut report: Report) -> Result<Report> {
+        let report = self
+            .blocking(move |falcon8| falcon8.receive_report(&mut report).map(|()| report))
+            .await?;
+        self.pause().await;
+
+        Ok(report)
+    }
+
+    async fn get_keys(&mut self) -> Result<Report> {
+        let report = Report::from_falcon(self.falcon8());
+
+        self.set_report(report).await?;
+        self.get_report(report).await
+    }
+}
+
+impl<T: Transport + Send + 'static> AsyncFalcon8<T> {
+    pub fn new(falcon8: Falcon8<T>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(falcon8)),
+        }
+    }
+
+    /// Locks the wrapped [`Falcon8`], to change its controls before an update.
+    ///
+    /// This waits for the running operation, if any, and holds off the next ones until the guard
+    /// is dropped.
+    pub async fn lock(&self) -> MutexGuard<'_, Falcon8<T>> {
+        self.inner.lock().await
+    }
+
+    /// Locks the [`Falcon8`] for a whole operation.
+    async fn session(&self) -> Session<T> {
+        Session {
+            falcon8: Some(Arc::clone(&self.inner).lock_owned().await),
+        }
+    }
+
+    pub async fn set_report(&mut self, report: Report) -> Result<()> {
+        self.session().await.set_report(report).await
+    }
+
+    pub async fn get_report(&mut self, report: Report) -> Result<Report> {
+        self.session().await.get_report(report).await
+    }
+
+    pub async fn finalize(&mut self) -> Result<()> {
+        self.session().await.set_report(Report::finalize()).await
+    }
+
+    /// See [`Falcon8::get_keys`].
+    pub async fn get_keys(&mut self) -> Result<Report> {
+        self.session().await.get_keys().await
+    }
+
+    /// See [`Falcon8::update_keys`].
+    pub async fn update_keys(&mut self) -> Result<()> {
+        let mut session = self.session().await;
+        let current = session.get_keys().await?;
+
+        for report in session.falcon8().key_update_reports(current)? {
+            session.set_report(report).await?;
+        }
+        session.falcon8().key_controls.dropped_shortcuts = [false; 8];
+
+        Ok(())
+    }
+
+    /// See [`Falcon8::update_leds`].
+    pub async fn update_leds(&mut self) -> Result<()> {
+        let mut session = self.session().await;
+        let current = session.get_keys().await?;
+
+        let report = session.falcon8().led_update_report(current)?;
+        session.set_report(report).await
+    }
+
+    /// See [`Falcon8::update_layer`].
+    pub async fn update_layer(&mut self, layer: Layer) -> Result<()> {
+        let mut session = self.session().await;
+
+        session.falcon8().active_layer = layer;
+        session.set_report(Report::layer_write(layer)).await
+    }
+}
+
+impl<T: UsbContext + 'static> AsyncFalcon8<UsbTransport<T>> {
+    /// Listens to the pad's interrupt IN endpoints, like [`Falcon8::events`].
+    ///
+    /// The reading thread only holds the lock for one round over the endpoints at a time, so
+    /// updates waiting for it go through in between. It stops after an error, or once the stream
+    /// is dropped.
+    pub async fn events(&self) -> Result<EventStream> {
+        let mut reader = self
+            .session()
+            .await
+            .blocking(|falcon8| falcon8.input_reader())
+            .await?;
+
+        let inner = Arc::clone(&self.inner);
+        let (sender, events) = mpsc::unbounded_channel();
+
+        std::thread::spawn(move || {
+            // a pad without interrupt endpoints never sends anything
+            while !sender.is_closed() && !reader.endpoints.is_empty() {
+                let read = inner
+                    .blocking_lock()
+                    .read_input(&mut reader, std::time::Duration::from_millis(10));
+
+                match read {
+                    Ok(read) => {
+                        for event in read {
+                            let _ = sender.send(Ok(event));
+                        }
+                    }
+                    Err(err) => {
+                        let _ = sender.send(Err(err));
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(EventStream { events })
+    }
+}
+
+impl EventStream {
+    /// Waits for the next event, `None` meaning the reading thread has stopped.
+    pub async fn next(&mut self) -> Option<Result<InputEvent>> {
+        self.events.recv().await
+    }
+}
+
+impl<T: Transport + Send + 'static> From<Falcon8<T>> for AsyncFalcon8<T> {
+    fn from(falcon8: Falcon8<T>) -> Self {
+        Self::new(falcon8)
+    }
+}
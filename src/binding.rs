@@ -0,0 +1,207 @@
+use std::str::FromStr;
+
+use crate::{Error, KeyCode, KeyPress, Macro, MacroData, Repetition, Result};
+
+/// Modifier keys in the order of their bits in a boot keyboard report.
+pub(crate) const MODIFIERS: [KeyCode; 8] = [
+    KeyCode::Leftctrl,
+    KeyCode::Leftshift,
+    KeyCode::Leftalt,
+    KeyCode::Leftmeta,
+    KeyCode::Rightctrl,
+    KeyCode::Rightshift,
+    KeyCode::Rightalt,
+    KeyCode::Rightmeta,
+];
+
+/// A set of modifier keys, laid out like the modifier byte of a boot keyboard report.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Modifiers(u8);
+
+/// A key pressed along with modifiers, like Ctrl+Shift+T.
+///
+/// The pad binds a single byte to a key, so only bindings without modifiers, or made of one
+/// modifier alone, are sent as key codes. Any other binding is played by a macro from the key's
+/// macro slot, see [`Binding::to_macro`].
+///
+/// Bindings parse from key code names joined by `+`, e.g. `ctrl+shift+t` or `alt+vol+`, and
+/// display the same way with the [`KeyCode`] variant names.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Binding {
+    pub modifiers: Modifiers,
+    pub key_code: KeyCode,
+}
+
+impl Modifiers {
+    pub const NONE: Modifiers = Modifiers(0);
+    pub const CTRL: Modifiers = Modifiers(1 << 0);
+    pub const SHIFT: Modifiers = Modifiers(1 << 1);
+    pub const ALT: Modifiers = Modifiers(1 << 2);
+    pub const META: Modifiers = Modifiers(1 << 3);
+    pub const RIGHT_CTRL: Modifiers = Modifiers(1 << 4);
+    pub const RIGHT_SHIFT: Modifiers = Modifiers(1 << 5);
+    pub const RIGHT_ALT: Modifiers = Modifiers(1 << 6);
+    pub const RIGHT_META: Modifiers = Modifiers(1 << 7);
+
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    pub const fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn contains(&self, other: Modifiers) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: Modifiers) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: Modifiers) {
+        self.0 &= !other.0;
+    }
+
+    /// The modifier a key code is, if it is one.
+    pub fn of(key_code: KeyCode) -> Option<Self> {
+        // the plain usages of the left modifiers count as the pad's own codes
+        let key_code = match key_code {
+            KeyCode::_LEFTCTRL => KeyCode::Leftctrl,
+            KeyCode::_LEFTSHIFT => KeyCode::Leftshift,
+            KeyCode::_LEFTALT => KeyCode::Leftalt,
+            KeyCode::_LEFTMETA => KeyCode::Leftmeta,
+            key_code => key_code,
+        };
+
+        MODIFIERS
+            .iter()
+            .position(|modifier| *modifier == key_code)
+            .map(|bit| Self(1 << bit))
+    }
+
+    /// Key codes of the modifiers in the set, in bit order.
+    pub fn key_codes(&self) -> impl DoubleEndedIterator<Item = KeyCode> {
+        let bits = self.0;
+        MODIFIERS
+            .into_iter()
+            .enumerate()
+            .filter(move |(bit, _)| bits & (1 << bit) != 0)
+            .map(|(_, key_code)| key_code)
+    }
+}
+
+impl std::ops::BitOr for Modifiers {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl std::ops::BitOrAssign for Modifiers {
+    fn bitor_assign(&mut self, other: Self) {
+        self.insert(other);
+    }
+}
+
+impl Binding {
+    pub fn new(modifiers: Modifiers, key_code: KeyCode) -> Self {
+        Self {
+            modifiers,
+            key_code,
+        }
+    }
+
+    /// The byte the firmware can bind this to directly, if there is one.
+    pub fn key_code(&self) -> Option<KeyCode> {
+        let modifiers = match self.key_code {
+            KeyCode::Disable => self.modifiers,
+            _ if self.modifiers.is_empty() => return Some(self.key_code),
+            _ => return None,
+        };
+
+        // a lone modifier is a key code of its own
+        let mut key_codes = modifiers.key_codes();
+        match (key_codes.next(), key_codes.next()) {
+            (Some(key_code), None) => Some(key_code),
+            (None, _) => Some(KeyCode::Disable),
+            _ => None,
+        }
+    }
+
+    /// A macro pressing the modifiers, then tapping the key and releasing the modifiers in
+    /// reverse. It uses [`Repetition::UntilNextKeyPressed`], the profile default.
+    pub fn to_macro(&self) -> Macro {
+        let step = |key_press, delay, key_code| MacroData {
+            key_press,
+            delay,
+            key_code,
+        };
+
+        let mut r#macro = Macro::new(Repetition::UntilNextKeyPressed);
+        for modifier in self.modifiers.key_codes() {
+            r#macro.data.push(step(KeyPress::Down, 0, modifier));
+        }
+        if self.key_code != KeyCode::Disable {
+            r#macro.data.push(step(KeyPress::Down, 0, self.key_code));
+            // give the host a chance to see the key held before letting go of it
+            r#macro.data.push(step(KeyPress::Up, 1, self.key_code));
+        }
+        for modifier in self.modifiers.key_codes().rev() {
+            r#macro.data.push(step(KeyPress::Up, 0, modifier));
+        }
+
+        r#macro
+    }
+}
+
+impl From<KeyCode> for Binding {
+    fn from(key_code: KeyCode) -> Self {
+        Self::new(Modifiers::NONE, key_code)
+    }
+}
+
+/// Modifiers pressed on their own.
+impl From<Modifiers> for Binding {
+    fn from(modifiers: Modifiers) -> Self {
+        Self::new(modifiers, KeyCode::Disable)
+    }
+}
+
+impl FromStr for Binding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut modifiers = Modifiers::NONE;
+        let mut rest = s.trim();
+
+        // a `+` only separates a modifier from what follows, so `vol+` and `kp+` stay keys
+        while let Some((name, tail)) = rest.split_once('+') {
+            let modifier = name.parse().ok().and_then(Modifiers::of);
+            match modifier {
+                Some(modifier) if !tail.is_empty() => {
+                    modifiers |= modifier;
+                    rest = tail;
+                }
+                _ => break,
+            }
+        }
+
+        Ok(Self::new(modifiers, rest.parse()?))
+    }
+}
+
+impl std::fmt::Display for Binding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for modifier in self.modifiers.key_codes() {
+            write!(f, "{modifier:?}+")?;
+        }
+        write!(f, "{:?}", self.key_code)
+    }
+}
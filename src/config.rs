@@ -0,0 +1,153 @@
+use crate::{
+    r#macro::macro_frames, Brightness, Color, DecodedReport, Falcon8, Flow, Key, KeyCode, LEDMode,
+    Layer, Macro, Mode, Report, Result, Transport,
+};
+
+/// Everything the pad stores for one layer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LayerConfig {
+    /// Key bindings, indexed by [`Key`]
+    pub key_codes: [KeyCode; 8],
+    /// Per-key colors used by [`LEDMode::Custom`], indexed by [`Key`]
+    pub key_colors: [Color; 8],
+
+    pub led_mode: LEDMode,
+    pub brightness: Brightness,
+    pub flow: Flow,
+    pub led_color: Color,
+
+    /// Macros, indexed by [`Key`]
+    pub macros: [Option<Macro>; 8],
+}
+
+/// The whole state of a pad, one [`LayerConfig`] per layer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceConfig {
+    pub layers: [LayerConfig; 5],
+}
+
+impl LayerConfig {
+    pub fn from_report(report: &DecodedReport) -> Self {
+        Self {
+            key_codes: report.key_codes,
+            key_colors: report.key_colors,
+
+            led_mode: report.led_mode,
+            brightness: report.brightness,
+            flow: report.flow,
+            led_color: report.led_color,
+
+            macros: Default::default(),
+        }
+    }
+
+    /// Writes the layer's settings into `report`, pointing keys with a macro at their macro slot.
+    pub fn apply(&self, report: &mut Report) {
+        report
+            .set_led_mode(self.led_mode)
+            .set_brightness(self.brightness)
+            .set_flow(self.flow)
+            .set_led_color(self.led_color);
+
+        for key in Key::ALL {
+            report.set_key(key, self.key_codes[key as usize]);
+            report.set_key_color(key, self.key_colors[key as usize]);
+
+            let slot = self.macros[key as usize]
+                .as_ref()
+                .map_or(0, |_| key.macro_slot());
+            report.set_macro(key, slot);
+        }
+    }
+}
+
+impl Default for LayerConfig {
+    fn default() -> Self {
+        // the default report only holds known values
+        Self::from_report(&Report::default().decode().unwrap())
+    }
+}
+
+impl DeviceConfig {
+    pub fn layer(&self, layer: Layer) -> &LayerConfig {
+        &self.layers[layer as usize - 1]
+    }
+
+    pub fn layer_mut(&mut self, layer: Layer) -> &mut LayerConfig {
+        &mut self.layers[layer as usize - 1]
+    }
+}
+
+impl<T: Transport> Falcon8<T> {
+    /// Reads the configuration of every layer, macros included.
+    ///
+    /// Only the macros of keys pointing at their own slot are read, as clearing a macro leaves its
+    /// frames behind. This takes a while on real hardware, as every macro is 3 report round trips.
+    pub fn read_config(&mut self) -> Result<DeviceConfig> {
+        let (mut config, macro_keys) = self.read_settings()?;
+
+        for (layer, key) in macro_keys {
+            config.layer_mut(layer).macros[key as usize] = Some(self.read_macro(layer, key)?);
+        }
+
+        Ok(config)
+    }
+
+    /// Reads the configuration of every layer without its macros, and which keys point at their
+    /// own macro slot.
+    pub(crate) fn read_settings(&mut self) -> Result<(DeviceConfig, Vec<(Layer, Key)>)> {
+        let mut config = DeviceConfig::default();
+        let mut macro_keys = Vec::new();
+
+        for layer in Layer::ALL {
+            let mut report = Report::empty().clear(Some(layer));
+            self.set_report(&report)?;
+            self.get_report(&mut report)?;
+
+            *config.layer_mut(layer) = LayerConfig::from_report(&report.decode()?);
+            macro_keys.extend(
+                Key::ALL
+                    .into_iter()
+                    .filter(|key| report.macro_slot(*key) == key.macro_slot())
+                    .map(|key| (layer, key)),
+            );
+        }
+
+        Ok((config, macro_keys))
+    }
+
+    /// Writes the configuration of every layer, then commits it all at once.
+    ///
+    /// Every macro is checked before anything is sent, so an invalid one leaves the pad as it was.
+    pub fn write_config(&mut self, config: &DeviceConfig) -> Result<()> {
+        let mut frames = Vec::new();
+        for layer in Layer::ALL {
+            let macros = Key::ALL
+                .into_iter()
+                .zip(&config.layer(layer).macros)
+                .filter_map(|(key, r#macro)| Some((key, r#macro.clone()?)))
+                .collect::<Vec<_>>();
+            frames.push(macro_frames(layer, &macros)?);
+        }
+
+        for (layer, frames) in Layer::ALL.into_iter().zip(frames) {
+            let layer_config = config.layer(layer);
+
+            let mut report = Report::empty().clear(Some(layer));
+            self.set_report(&report)?;
+            self.get_report(&mut report)?;
+
+            report.set_mode(Mode::KeyWrite).clear_end();
+            layer_config.apply(&mut report);
+            self.set_report(&report)?;
+
+            for frame in frames {
+                self.set_report(&frame)?;
+            }
+        }
+
+        self.finalize()
+    }
+}
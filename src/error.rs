@@ -0,0 +1,125 @@
+use crate::{DecodeError, DeviceInfo, SkipReason};
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The USB stack reported an error.
+    Usb(rusb::Error),
+    /// A transfer moved a different number of bytes than a full report.
+    ShortTransfer { expected: usize, actual: usize },
+    /// The requested configuration can't be expressed to the pad.
+    InvalidConfig { field: &'static str, reason: String },
+    /// A report from the pad holds a byte the crate has no meaning for.
+    UnknownValue {
+        field: &'static str,
+        offset: usize,
+        value: u8,
+    },
+    /// No Falcon-8 is connected, or none could be opened.
+    DeviceNotFound,
+    /// No pad passed a [`crate::DeviceSelector`], listing why each one was skipped.
+    NoMatchingDevice {
+        skipped: Vec<(DeviceInfo, SkipReason)>,
+    },
+    /// More than one pad passed a [`crate::DeviceSelector`].
+    AmbiguousDevice { matches: Vec<DeviceInfo> },
+    /// More than one hidraw node belongs to a Falcon-8 where one was expected.
+    #[cfg(target_os = "linux")]
+    AmbiguousHidraw { nodes: Vec<crate::HidrawNode> },
+    /// Reading or writing a file failed.
+    Io(std::io::Error),
+    /// A profile couldn't be parsed or serialized.
+    Profile(String),
+    /// A macro script couldn't be parsed, at a 1-based line and column.
+    Syntax {
+        line: usize,
+        column: usize,
+        reason: String,
+    },
+}
+
+impl Error {
+    pub(crate) fn invalid_config(field: &'static str, reason: impl Into<String>) -> Self {
+        Self::InvalidConfig {
+            field,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Usb(err) => write!(f, "usb error: {err}"),
+            Self::ShortTransfer { expected, actual } => {
+                write!(f, "transferred {actual} bytes, expected {expected}")
+            }
+            Self::InvalidConfig { field, reason } => write!(f, "invalid {field}: {reason}"),
+            Self::UnknownValue {
+                field,
+                offset,
+                value,
+            } => write!(
+                f,
+                "unknown {field} 0x{value:02X} at offset 0x{offset:02X} in device report"
+            ),
+            Self::DeviceNotFound => write!(f, "no Falcon-8 found"),
+            Self::NoMatchingDevice { skipped } => {
+                write!(f, "no Falcon-8 matches")?;
+                for (info, reason) in skipped {
+                    write!(f, "\n  {info}: {reason}")?;
+                }
+                Ok(())
+            }
+            Self::AmbiguousDevice { matches } => {
+                write!(f, "{} Falcon-8s match, narrow the selection", matches.len())?;
+                for info in matches {
+                    write!(f, "\n  {info}")?;
+                }
+                Ok(())
+            }
+            #[cfg(target_os = "linux")]
+            Self::AmbiguousHidraw { nodes } => {
+                write!(f, "{} Falcon-8s match, narrow the selection", nodes.len())?;
+                for node in nodes {
+                    write!(f, "\n  {node}")?;
+                }
+                Ok(())
+            }
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Profile(reason) => write!(f, "invalid profile: {reason}"),
+            Self::Syntax {
+                line,
+                column,
+                reason,
+            } => write!(f, "line {line}, column {column}: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Usb(err) => Some(err),
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(err: DecodeError) -> Self {
+        Self::UnknownValue {
+            field: err.field,
+            offset: err.offset,
+            value: err.value,
+        }
+    }
+}
+
+impl From<rusb::Error> for Error {
+    fn from(err: rusb::Error) -> Self {
+        Self::Usb(err)
+    }
+}
@@ -0,0 +1,320 @@
+//! Key presses reported by the pad on its interrupt IN endpoints.
+//!
+//! The pad shows up as a composite HID device:
+//!
+//! - interface 0 sends 8 byte boot keyboard reports (modifier bits, a reserved byte, then up to
+//!   6 pressed usages)
+//! - the other interfaces prefix their reports with a report id: `0x01` for the mouse (button
+//!   bits, then motion), `0x02` for consumer control (a little endian usage, 0 once released) and
+//!   `0x07` for the vendor report, which carries [`Mode::LayerWrite`] and the new layer when the
+//!   pad switches layers by itself.
+
+use std::{collections::VecDeque, time::Duration};
+
+use num_traits::FromPrimitive;
+use rusb::{Direction, TransferType, UsbContext};
+
+use crate::{
+    binding::MODIFIERS, keycode::CONSUMER_PAGE, Falcon8, Key, KeyCode, KeyPress, Layer, Mode,
+    Report, Result, UsbTransport,
+};
+
+const KEYBOARD_INTERFACE: u8 = 0;
+
+const MOUSE_REPORT: u8 = 0x01;
+const CONSUMER_REPORT: u8 = 0x02;
+const VENDOR_REPORT: u8 = 0x07;
+
+/// Mouse buttons in the order of their bits in a mouse report.
+const MOUSE_BUTTONS: [KeyCode; 5] = [
+    KeyCode::MouseLeftClick,
+    KeyCode::MouseRightClick,
+    KeyCode::MouseMiddleClick,
+    KeyCode::MouseBackward,
+    KeyCode::MouseForward,
+];
+
+/// Something the pad reported. `key` is the pad key bound to what was sent on the active layer,
+/// if any is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InputEvent {
+    Key {
+        key: Option<Key>,
+        key_code: KeyCode,
+        press: KeyPress,
+    },
+    Consumer {
+        key: Option<Key>,
+        usage: u16,
+        press: KeyPress,
+    },
+    MouseButton {
+        key: Option<Key>,
+        key_code: KeyCode,
+        press: KeyPress,
+    },
+    LayerSwitch(Layer),
+}
+
+/// Turns raw input reports into [`InputEvent`]s, remembering what is held down between reports.
+#[derive(Debug, Clone)]
+pub struct InputDecoder {
+    layer: Layer,
+    /// Key code bytes of every key, per layer
+    bindings: [[u8; 8]; 5],
+
+    modifiers: u8,
+    keys: Vec<u8>,
+    mouse_buttons: u8,
+    consumer: u16,
+}
+
+impl InputDecoder {
+    /// A decoder starting on `layer`, which knows no bindings until
+    /// [`InputDecoder::set_bindings`] is called.
+    pub fn new(layer: Layer) -> Self {
+        Self {
+            layer,
+            bindings: [[KeyCode::Disable as u8; 8]; 5],
+
+            modifiers: 0,
+            keys: Vec::new(),
+            mouse_buttons: 0,
+            consumer: 0,
+        }
+    }
+
+    pub fn layer(&self) -> Layer {
+        self.layer
+    }
+
+    /// Takes the key bindings of `layer` from a key report.
+    pub fn set_bindings(&mut self, layer: Layer, report: &Report) {
+        for key in Key::ALL {
+            self.bindings[layer as usize - 1][key as usize] = report[key.to_index()];
+        }
+    }
+
+    /// The key sending `key_code` on the active layer.
+    fn key(&self, key_code: KeyCode) -> Option<Key> {
+        let bindings = &self.bindings[self.layer as usize - 1];
+        Key::ALL
+            .into_iter()
+            .find(|key| bindings[*key as usize] == key_code as u8)
+    }
+
+    /// Decodes one report received on `interface`, ignoring anything it can't make sense of.
+    pub fn decode(&mut self, interface: u8, data: &[u8]) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+
+        if interface == KEYBOARD_INTERFACE {
+            if data.len() == 8 {
+                self.decode_keyboard(data, &mut events);
+            }
+            return events;
+        }
+
+        match data {
+            [MOUSE_REPORT, buttons, ..] => self.decode_mouse(*buttons, &mut events),
+            [CONSUMER_REPORT, low, high, ..] => {
+                self.decode_consumer(u16::from_le_bytes([*low, *high]), &mut events)
+            }
+            [VENDOR_REPORT, mode, layer, ..] if *mode == Mode::LayerWrite as u8 => {
+                if let Some(layer) = Layer::from_u8(*layer) {
+                    self.layer = layer;
+                    events.push(InputEvent::LayerSwitch(layer));
+                }
+            }
+            _ => {}
+        }
+
+        events
+    }
+
+    fn decode_keyboard(&mut self, data: &[u8], events: &mut Vec<InputEvent>) {
+        let key_event = |key_code: KeyCode, press: KeyPress| InputEvent::Key {
+            key: self.key(key_code),
+            key_code,
+            press,
+        };
+
+        let changed = self.modifiers ^ data[0];
+        for (bit, key_code) in MODIFIERS.into_iter().enumerate() {
+            if changed & (1 << bit) != 0 {
+                let press = if data[0] & (1 << bit) != 0 {
+                    KeyPress::Down
+                } else {
+                    KeyPress::Up
+                };
+                events.push(key_event(key_code, press));
+            }
+        }
+
+        // usages 1 to 3 are roll over errors, not keys
+        let keys = data[2..]
+            .iter()
+            .copied()
+            .filter(|usage| *usage > KeyCode::ErrUndefined as u8)
+            .collect::<Vec<_>>();
+
+        for usage in self.keys.iter().filter(|usage| !keys.contains(usage)) {
+            if let Some(key_code) = KeyCode::from_u8(*usage) {
+                events.push(key_event(key_code, KeyPress::Up));
+            }
+        }
+        for usage in keys.iter().filter(|usage| !self.keys.contains(usage)) {
+            if let Some(key_code) = KeyCode::from_u8(*usage) {
+                events.push(key_event(key_code, KeyPress::Down));
+            }
+        }
+
+        self.modifiers = data[0];
+        self.keys = keys;
+    }
+
+    fn decode_mouse(&mut self, buttons: u8, events: &mut Vec<InputEvent>) {
+        let changed = self.mouse_buttons ^ buttons;
+        for (bit, key_code) in MOUSE_BUTTONS.into_iter().enumerate() {
+            if changed & (1 << bit) != 0 {
+                events.push(InputEvent::MouseButton {
+                    key: self.key(key_code),
+                    key_code,
+                    press: if buttons & (1 << bit) != 0 {
+                        KeyPress::Down
+                    } else {
+                        KeyPress::Up
+                    },
+                });
+            }
+        }
+
+        self.mouse_buttons = buttons;
+    }
+
+    fn decode_consumer(&mut self, usage: u16, events: &mut Vec<InputEvent>) {
+        let consumer_event = |usage: u16, press: KeyPress| InputEvent::Consumer {
+            key: KeyCode::from_hid_usage(CONSUMER_PAGE, usage)
+                .and_then(|key_code| self.key(key_code)),
+            usage,
+            press,
+        };
+
+        if usage == self.consumer {
+            return;
+        }
+        if self.consumer != 0 {
+            events.push(consumer_event(self.consumer, KeyPress::Up));
+        }
+        if usage != 0 {
+            events.push(consumer_event(usage, KeyPress::Down));
+        }
+
+        self.consumer = usage;
+    }
+}
+
+/// Endpoints and decoder state kept between reads of the pad's input.
+pub(crate) struct InputReader {
+    /// Interface and address of every interrupt IN endpoint
+    pub(crate) endpoints: Vec<(u8, u8)>,
+    decoder: InputDecoder,
+}
+
+/// Blocking iterator over the pad's [`InputEvent`]s, see [`Falcon8::events`].
+pub struct Events<'a, T: UsbContext> {
+    falcon8: &'a mut Falcon8<UsbTransport<T>>,
+    reader: InputReader,
+    pending: VecDeque<InputEvent>,
+}
+
+impl<T: UsbContext> Falcon8<UsbTransport<T>> {
+    /// Listens to the pad's interrupt IN endpoints.
+    ///
+    /// The key bindings of every layer are read first, so events can name the [`Key`] that sent
+    /// them. Layer switches reported by the pad update [`Falcon8::active_layer`].
+    pub fn events(&mut self) -> Result<Events<'_, T>> {
+        Ok(Events {
+            reader: self.input_reader()?,
+            falcon8: self,
+            pending: VecDeque::new(),
+        })
+    }
+
+    pub(crate) fn input_reader(&mut self) -> Result<InputReader> {
+        let mut decoder = InputDecoder::new(self.active_layer);
+        for layer in Layer::ALL {
+            let mut report = Report::empty().clear(Some(layer));
+            self.set_report(&report)?;
+            self.get_report(&mut report)?;
+            decoder.set_bindings(layer, &report);
+        }
+
+        let config_desc = self.transport.device.config_descriptor(0)?;
+        let mut endpoints = Vec::new();
+        for interface in config_desc.interfaces() {
+            for interface_desc in interface.descriptors() {
+                for endpoint_desc in interface_desc.endpoint_descriptors() {
+                    if endpoint_desc.direction() == Direction::In
+                        && endpoint_desc.transfer_type() == TransferType::Interrupt
+                    {
+                        endpoints
+                            .push((interface_desc.interface_number(), endpoint_desc.address()));
+                    }
+                }
+            }
+        }
+
+        Ok(InputReader { endpoints, decoder })
+    }
+
+    /// Reads every endpoint once, waiting up to `timeout` on each.
+    pub(crate) fn read_input(
+        &mut self,
+        reader: &mut InputReader,
+        timeout: Duration,
+    ) -> Result<Vec<InputEvent>> {
+        let mut buf = [0; 64];
+        let mut events = Vec::new();
+
+        // poll the endpoints in turn, as one of them may stay silent for good
+        for (interface, address) in &reader.endpoints {
+            match self
+                .transport
+                .handle
+                .read_interrupt(*address, &mut buf, timeout)
+            {
+                Ok(size) => events.extend(reader.decoder.decode(*interface, &buf[..size])),
+                Err(rusb::Error::Timeout) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        self.active_layer = reader.decoder.layer();
+
+        Ok(events)
+    }
+}
+
+impl<T: UsbContext> Iterator for Events<'_, T> {
+    type Item = Result<InputEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+
+            if self.reader.endpoints.is_empty() {
+                return None;
+            }
+
+            match self
+                .falcon8
+                .read_input(&mut self.reader, Duration::from_millis(10))
+            {
+                Ok(events) => self.pending.extend(events),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! The Linux hidraw backend.
+//!
+//! Feature reports go through the `HIDIOCSFEATURE`/`HIDIOCGFEATURE` ioctls of the pad's
+//! `/dev/hidraw*` node, so the kernel HID driver stays bound and the pad keeps typing while it is
+//! configured, unlike with [`crate::UsbTransport`] which detaches it.
+
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{Error, Falcon8, Report, Result, Transport, PID, REPORT_DELAY, VID};
+
+/// USB interface the pad takes feature reports on
+const REPORT_INTERFACE: u8 = 2;
+
+/// Bus type of USB HID devices in `HID_ID`
+const BUS_USB: u16 = 0x0003;
+
+/// Anything that exchanges feature reports like a hidraw file descriptor, the report id being
+/// the first byte of `data` both ways.
+pub trait FeatureDevice {
+    fn set_feature(&mut self, data: &[u8]) -> io::Result<usize>;
+    fn get_feature(&mut self, data: &mut [u8]) -> io::Result<usize>;
+}
+
+/// An open `/dev/hidraw*` node.
+#[derive(Debug)]
+pub struct HidrawFile {
+    file: File,
+}
+
+/// A hidraw node of a Falcon-8, as described by sysfs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HidrawNode {
+    /// Device node, e.g. `/dev/hidraw3`
+    pub path: PathBuf,
+    /// Physical location, e.g. `usb-0000:00:14.0-2.1/input2`
+    pub phys: String,
+    /// Serial number, empty when the pad has none
+    pub uniq: String,
+}
+
+/// Exchanges reports through a [`FeatureDevice`], a [`HidrawFile`] unless faked.
+#[derive(Debug)]
+pub struct HidrawTransport<D: FeatureDevice = HidrawFile> {
+    pub device: D,
+}
+
+/// The `_IOC` layout of ioctl request numbers, which a few architectures change.
+pub(crate) mod ioc {
+    #[cfg(any(
+        target_arch = "powerpc",
+        target_arch = "powerpc64",
+        target_arch = "mips",
+        target_arch = "mips32r6",
+        target_arch = "mips64",
+        target_arch = "mips64r6",
+        target_arch = "sparc",
+        target_arch = "sparc64"
+    ))]
+    mod layout {
+        pub const SIZE_BITS: u32 = 13;
+        pub const WRITE: u32 = 4;
+    }
+
+    #[cfg(not(any(
+        target_arch = "powerpc",
+        target_arch = "powerpc64",
+        target_arch = "mips",
+        target_arch = "mips32r6",
+        target_arch = "mips64",
+        target_arch = "mips64r6",
+        target_arch = "sparc",
+        target_arch = "sparc64"
+    )))]
+    mod layout {
+        pub const SIZE_BITS: u32 = 14;
+        pub const WRITE: u32 = 1;
+    }
+
+    /// `_IOC_WRITE`, the caller passes data in
+    pub const WRITE: u32 = layout::WRITE;
+    /// `_IOC_READ`, the kernel passes data out
+    pub const READ: u32 = 2;
+
+    /// `_IOC(dir, kind, nr, size)`
+    pub const fn request(dir: u32, kind: u8, nr: u32, size: usize) -> u32 {
+        (dir << (16 + layout::SIZE_BITS)) | ((size as u32) << 16) | ((kind as u32) << 8) | nr
+    }
+}
+
+/// `_IOC(_IOC_READ | _IOC_WRITE, 'H', nr, len)`
+fn hid_ioctl(nr: u32, len: usize) -> u32 {
+    ioc::request(ioc::READ | ioc::WRITE, b'H', nr, len)
+}
+
+impl HidrawFile {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(Error::Io)?;
+
+        Ok(Self { file })
+    }
+
+    fn ioctl(&self, nr: u32, data: *mut u8, len: usize) -> io::Result<usize> {
+        // SAFETY: the feature ioctls access at most `len` bytes of `data`
+        let size = unsafe { libc::ioctl(self.file.as_raw_fd(), hid_ioctl(nr, len) as _, data) };
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(size as usize)
+    }
+}
+
+impl FeatureDevice for HidrawFile {
+    fn set_feature(&mut self, data: &[u8]) -> io::Result<usize> {
+        // HIDIOCSFEATURE only reads the buffer
+        self.ioctl(0x06, data.as_ptr().cast_mut(), data.len())
+    }
+
+    fn get_feature(&mut self, data: &mut [u8]) -> io::Result<usize> {
+        self.ioctl(0x07, data.as_mut_ptr(), data.len())
+    }
+}
+
+impl std::fmt::Display for HidrawNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.path.display(), self.phys)?;
+
+        if !self.uniq.is_empty() {
+            write!(f, " serial {}", self.uniq)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl HidrawNode {
+    /// Finds the report interface of every connected Falcon-8.
+    pub fn find() -> Result<Vec<Self>> {
+        Self::find_in(Path::new("/sys"), Path::new("/dev"))
+    }
+
+    /// [`HidrawNode::find`] on another sysfs and device tree, `sys` holding `class/hidraw`.
+    pub fn find_in(sys: &Path, dev: &Path) -> Result<Vec<Self>> {
+        let class = sys.join("class/hidraw");
+        let entries = match std::fs::read_dir(&class) {
+            Ok(entries) => entries,
+            // no hidraw driver loaded
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(Error::Io(err)),
+        };
+
+        let mut nodes = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(Error::Io)?;
+            let Ok(uevent) = std::fs::read_to_string(entry.path().join("device/uevent")) else {
+                continue;
+            };
+
+            if let Some(node) = Self::from_uevent(&uevent, dev.join(entry.file_name())) {
+                nodes.push(node);
+            }
+        }
+
+        nodes.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(nodes)
+    }
+
+    /// Reads the `uevent` of a hidraw node's HID device, keeping it if it is the report
+    /// interface of a Falcon-8.
+    fn from_uevent(uevent: &str, path: PathBuf) -> Option<Self> {
+        let mut id = None;
+        let mut phys = None;
+        let mut uniq = String::new();
+
+        for line in uevent.lines() {
+            match line.split_once('=') {
+                Some(("HID_ID", value)) => id = Some(value),
+                Some(("HID_PHYS", value)) => phys = Some(value.to_string()),
+                Some(("HID_UNIQ", value)) => uniq = value.to_string(),
+                _ => {}
+            }
+        }
+
+        // HID_ID is bus:vendor:product in hex
+        let mut id = id?.split(':').map(|part| u32::from_str_radix(part, 16));
+        let (Some(Ok(bus)), Some(Ok(vid)), Some(Ok(pid))) = (id.next(), id.next(), id.next())
+        else {
+            return None;
+        };
+        if bus != BUS_USB as u32 || vid != VID as u32 || pid != PID as u32 {
+            return None;
+        }
+
+        let phys = phys?;
+        if !phys.ends_with(&format!("/input{REPORT_INTERFACE}")) {
+            return None;
+        }
+
+        Some(Self { path, phys, uniq })
+    }
+}
+
+impl HidrawTransport {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(HidrawFile::open(path)?))
+    }
+}
+
+impl<D: FeatureDevice> HidrawTransport<D> {
+    pub fn new(device: D) -> Self {
+        Self { device }
+    }
+}
+
+impl<D: FeatureDevice> Transport for HidrawTransport<D> {
+    fn set_report(&mut self, report: &Report) -> Result<()> {
+        let size = self
+            .device
+            .set_feature(report.as_ref())
+            .map_err(Error::Io)?;
+        if size != report.len() {
+            return Err(Error::ShortTransfer {
+                expected: report.len(),
+                actual: size,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn get_report(&mut self, report: &mut Report) -> Result<()> {
+        // the kernel takes the report id to request from the first byte
+        report[0] = 0x07;
+
+        let size = self
+            .device
+            .get_feature(report.as_mut())
+            .map_err(Error::Io)?;
+        if size != report.len() {
+            return Err(Error::ShortTransfer {
+                expected: report.len(),
+                actual: size,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn report_delay(&self) -> Duration {
+        REPORT_DELAY
+    }
+}
+
+impl Falcon8<HidrawTransport> {
+    /// Opens the first Falcon-8 found through hidraw, leaving the kernel driver bound.
+    pub fn open_hidraw() -> Result<Self> {
+        let node = HidrawNode::find()?
+            .into_iter()
+            .next()
+            .ok_or(Error::DeviceNotFound)?;
+
+        Ok(Self::with_transport(HidrawTransport::open(node.path)?))
+    }
+}
@@ -0,0 +1,207 @@
+use std::{
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    time::Duration,
+};
+
+use rusb::{Context, Device, Hotplug, HotplugBuilder, Registration, UsbContext};
+
+use crate::{
+    DeviceConfig, DeviceInfo, DeviceSelector, Error, Falcon8, Result, Transport, UsbTransport, PID,
+    VID,
+};
+
+/// A pad being plugged in or unplugged.
+///
+/// Hosts resuming from suspend usually report their pads as leaving and arriving again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotplugEvent {
+    Arrived(DeviceInfo),
+    Left(DeviceInfo),
+}
+
+/// Anything delivering [`HotplugEvent`]s, real or fake.
+pub trait EventSource {
+    /// Waits up to `timeout` for the next event.
+    fn next_event(&mut self, timeout: Duration) -> Result<Option<HotplugEvent>>;
+}
+
+/// Events sent by hand, which is mostly useful to drive a [`HotplugWatcher`] in tests.
+impl EventSource for Receiver<HotplugEvent> {
+    fn next_event(&mut self, timeout: Duration) -> Result<Option<HotplugEvent>> {
+        match self.recv_timeout(timeout) {
+            Ok(event) => Ok(Some(event)),
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => Ok(None),
+        }
+    }
+}
+
+/// Hotplug events of Falcon-8s from libusb, starting with the pads already connected.
+pub struct UsbEventSource<T: UsbContext = Context> {
+    context: T,
+    events: Receiver<HotplugEvent>,
+    _registration: Registration<T>,
+}
+
+struct Callback {
+    events: Sender<HotplugEvent>,
+}
+
+impl<T: UsbContext> Hotplug<T> for Callback {
+    fn device_arrived(&mut self, device: Device<T>) {
+        let _ = self
+            .events
+            .send(HotplugEvent::Arrived(DeviceInfo::of(&device)));
+    }
+
+    fn device_left(&mut self, device: Device<T>) {
+        let _ = self
+            .events
+            .send(HotplugEvent::Left(DeviceInfo::of(&device)));
+    }
+}
+
+impl UsbEventSource {
+    pub fn new() -> Result<Self> {
+        Self::with_context(Context::new()?)
+    }
+}
+
+impl<T: UsbContext> UsbEventSource<T> {
+    /// Registers for hotplug events, failing with [`rusb::Error::NotSupported`] on platforms
+    /// libusb has no hotplug support for.
+    pub fn with_context(context: T) -> Result<Self> {
+        if !rusb::has_hotplug() {
+            return Err(Error::Usb(rusb::Error::NotSupported));
+        }
+
+        let (sender, events) = mpsc::channel();
+
+        let mut builder = HotplugBuilder::new();
+        builder.vendor_id(VID).product_id(PID).enumerate(true);
+        let registration = builder.register(&context, Box::new(Callback { events: sender }))?;
+
+        Ok(Self {
+            context,
+            events,
+            _registration: registration,
+        })
+    }
+}
+
+impl<T: UsbContext> EventSource for UsbEventSource<T> {
+    fn next_event(&mut self, timeout: Duration) -> Result<Option<HotplugEvent>> {
+        // callbacks only run while libusb handles events
+        if let Ok(event) = self.events.try_recv() {
+            return Ok(Some(event));
+        }
+
+        self.context.handle_events(Some(timeout))?;
+
+        Ok(self.events.try_recv().ok())
+    }
+}
+
+type Open<T> = Box<dyn FnMut(&DeviceInfo) -> Result<Falcon8<T>>>;
+type Reapply<T> = Box<dyn FnMut(&mut Falcon8<T>) -> Result<()>>;
+
+/// Follows pads coming and going, optionally opening each one that arrives and restoring its
+/// configuration.
+pub struct HotplugWatcher<S: EventSource, T: Transport = UsbTransport<Context>> {
+    source: S,
+    open: Option<Open<T>>,
+    reapply: Option<Reapply<T>>,
+    pads: Vec<(DeviceInfo, Falcon8<T>)>,
+}
+
+impl<S: EventSource, T: Transport> HotplugWatcher<S, T> {
+    /// Watches `source` without opening anything.
+    pub fn new(source: S) -> Self {
+        Self {
+            source,
+            open: None,
+            reapply: None,
+            pads: Vec::new(),
+        }
+    }
+
+    /// Opens every pad that arrives with `open`, keeping it until it leaves.
+    pub fn reopen_with(
+        mut self,
+        open: impl FnMut(&DeviceInfo) -> Result<Falcon8<T>> + 'static,
+    ) -> Self {
+        self.open = Some(Box::new(open));
+        self
+    }
+
+    /// Runs `reapply` on every pad right after it was opened.
+    pub fn reapply_with(
+        mut self,
+        reapply: impl FnMut(&mut Falcon8<T>) -> Result<()> + 'static,
+    ) -> Self {
+        self.reapply = Some(Box::new(reapply));
+        self
+    }
+
+    /// Writes `config` to every pad right after it was opened.
+    pub fn reapply_config(self, config: DeviceConfig) -> Self {
+        self.reapply_with(move |falcon8| falcon8.write_config(&config))
+    }
+
+    /// Applies `profile` to every pad right after it was opened.
+    #[cfg(feature = "serde")]
+    pub fn reapply_profile(self, profile: crate::profile::Profile) -> Self {
+        self.reapply_with(move |falcon8| falcon8.apply_profile(&profile))
+    }
+
+    /// The pads currently open, in the order they arrived.
+    pub fn pads(&self) -> &[(DeviceInfo, Falcon8<T>)] {
+        &self.pads
+    }
+
+    pub fn pads_mut(&mut self) -> &mut [(DeviceInfo, Falcon8<T>)] {
+        &mut self.pads
+    }
+
+    /// Waits up to `timeout` for the next event and handles it.
+    ///
+    /// A pad that fails to re-open or to have its configuration re-applied is dropped, the error
+    /// being returned in place of its arrival.
+    pub fn poll(&mut self, timeout: Duration) -> Result<Option<HotplugEvent>> {
+        let Some(event) = self.source.next_event(timeout)? else {
+            return Ok(None);
+        };
+
+        match &event {
+            HotplugEvent::Arrived(info) => {
+                // an arrival without a leave means the old handle is stale
+                self.pads.retain(|(pad, _)| !pad.same_device(info));
+
+                if let Some(open) = &mut self.open {
+                    let mut falcon8 = open(info)?;
+                    if let Some(reapply) = &mut self.reapply {
+                        reapply(&mut falcon8)?;
+                    }
+                    self.pads.push((info.clone(), falcon8));
+                }
+            }
+            HotplugEvent::Left(info) => {
+                self.pads.retain(|(pad, _)| !pad.same_device(info));
+            }
+        }
+
+        Ok(Some(event))
+    }
+}
+
+impl<S: EventSource> HotplugWatcher<S> {
+    /// Opens and claims every Falcon-8 that arrives, through a [`DeviceSelector`] on its bus
+    /// and address.
+    pub fn reopen(self) -> Self {
+        self.reopen_with(|info| {
+            DeviceSelector::new()
+                .bus(info.bus)
+                .address(info.address)
+                .open()
+        })
+    }
+}
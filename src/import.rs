@@ -0,0 +1,175 @@
+//! Reading key events from the logs of `evtest` and `libinput record`.
+//!
+//! The events feed a [`crate::Recorder`] like those of a live device do, so a sequence captured on
+//! any machine can be turned into a macro offline.
+
+use std::time::Duration;
+
+use crate::{Error, EvdevEvent, Result};
+
+fn syntax(line: usize, column: usize, reason: impl Into<String>) -> Error {
+    Error::Syntax {
+        line,
+        column,
+        reason: reason.into(),
+    }
+}
+
+/// Parses `seconds.microseconds`, as evtest prints times.
+fn parse_time(time: &str) -> Option<Duration> {
+    let (secs, micros) = time.split_once('.')?;
+    if micros.len() != 6 {
+        return None;
+    }
+
+    Some(Duration::from_secs(secs.parse().ok()?) + Duration::from_micros(micros.parse().ok()?))
+}
+
+impl EvdevEvent {
+    /// The events of an `evtest` log, made of lines like
+    /// `Event: time 1700000000.123456, type 1 (EV_KEY), code 30 (KEY_A), value 1`. Anything else,
+    /// like the device description evtest starts with, is skipped.
+    pub fn from_evtest(log: &str) -> Result<Vec<Self>> {
+        let mut events = Vec::new();
+
+        for (index, line) in log.lines().enumerate() {
+            let Some(fields) = line.trim_start().strip_prefix("Event: time ") else {
+                continue;
+            };
+            let column = |field: &str| field.as_ptr() as usize - line.as_ptr() as usize + 1;
+            let line_number = index + 1;
+
+            let mut fields = fields.split(", ");
+            let time = fields.next().unwrap_or_default();
+            let time = parse_time(time).ok_or_else(|| {
+                syntax(line_number, column(time), "expected a time like 12.345678")
+            })?;
+
+            // `type T (EV_...)`, `code C (...)` and `value V`, none for the SYN_REPORT separators
+            let mut number = |name: &str| -> Result<Option<i64>> {
+                let Some(field) = fields.next() else {
+                    return Ok(None);
+                };
+                let Some(value) = field
+                    .strip_prefix(name)
+                    .and_then(|rest| rest.strip_prefix(' '))
+                else {
+                    return Ok(None);
+                };
+
+                value
+                    .split(' ')
+                    .next()
+                    // evtest prints some values, like scan codes, in hex
+                    .and_then(|value| {
+                        value
+                            .parse()
+                            .ok()
+                            .or_else(|| i64::from_str_radix(value, 16).ok())
+                    })
+                    .map(Some)
+                    .ok_or_else(|| {
+                        syntax(
+                            line_number,
+                            column(field),
+                            format!("expected a number after {name}"),
+                        )
+                    })
+            };
+
+            let (Some(kind), Some(code), Some(value)) =
+                (number("type")?, number("code")?, number("value")?)
+            else {
+                continue;
+            };
+            let (Ok(kind), Ok(code), Ok(value)) = (
+                u16::try_from(kind),
+                u16::try_from(code),
+                i32::try_from(value),
+            ) else {
+                return Err(syntax(line_number, 1, "type, code or value out of range"));
+            };
+
+            events.push(Self {
+                time,
+                kind,
+                code,
+                value,
+            });
+        }
+
+        Ok(events)
+    }
+
+    /// The events of a `libinput record` YAML log, ordered by time when it holds more than one
+    /// device. Each event is a `[seconds, microseconds, type, code, value]` line under a device's
+    /// `events:`.
+    pub fn from_libinput_record(log: &str) -> Result<Vec<Self>> {
+        let mut events = Vec::new();
+        let mut in_events = false;
+
+        for (index, line) in log.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed == "events:" {
+                in_events = true;
+                continue;
+            }
+            if trimmed.starts_with("- node:") {
+                in_events = false;
+                continue;
+            }
+
+            let Some(list) = trimmed.strip_prefix("- [").filter(|_| in_events) else {
+                continue;
+            };
+            let column = line.len() - line.trim_start().len() + 1;
+            let list = list.split('#').next().unwrap_or_default().trim_end();
+            let numbers = list
+                .strip_suffix(']')
+                .map(|list| {
+                    list.split(',')
+                        .map(|number| number.trim().parse::<i64>().ok())
+                        .collect::<Option<Vec<_>>>()
+                })
+                .ok_or_else(|| syntax(index + 1, column, "expected a closing ]"))?;
+
+            let event = numbers.and_then(|numbers| match numbers[..] {
+                [secs, micros, kind, code, value] => Some(Self {
+                    time: Duration::from_secs(secs.try_into().ok()?)
+                        + Duration::from_micros(micros.try_into().ok()?),
+                    kind: kind.try_into().ok()?,
+                    code: code.try_into().ok()?,
+                    value: value.try_into().ok()?,
+                }),
+                _ => None,
+            });
+            events.push(event.ok_or_else(|| {
+                syntax(
+                    index + 1,
+                    column,
+                    "expected [seconds, microseconds, type, code, value]",
+                )
+            })?);
+        }
+
+        // devices are listed one after the other
+        events.sort_by_key(|event| event.time);
+        Ok(events)
+    }
+
+    /// The events of either kind of log, told apart by their content.
+    pub fn from_log(log: &str) -> Result<Vec<Self>> {
+        let lines = || log.lines().map(str::trim);
+
+        if lines().any(|line| line.starts_with("Event: time ")) {
+            Self::from_evtest(log)
+        } else if lines().any(|line| line == "events:") {
+            Self::from_libinput_record(log)
+        } else {
+            Err(Error::invalid_config(
+                "log",
+                "expected an evtest log or a libinput recording",
+            ))
+        }
+    }
+}
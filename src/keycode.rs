@@ -1,4 +1,170 @@
+use std::str::FromStr;
+
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive as _;
+
+use crate::{Error, Result};
+
+/// HID usage pages key codes are sent on
+const KEYBOARD_PAGE: u16 = 0x07;
+const BUTTON_PAGE: u16 = 0x09;
+pub(crate) const CONSUMER_PAGE: u16 = 0x0C;
+
+/// Linux `KEY_*` codes of the keyboard usages, 0 where there is none, as the kernel's HID driver
+/// maps them.
+#[rustfmt::skip]
+const EVDEV_KEYBOARD: [u16; 256] = [
+      0,   0,   0,   0,  30,  48,  46,  32,  18,  33,  34,  35,  23,  36,  37,  38,
+     50,  49,  24,  25,  16,  19,  31,  20,  22,  47,  17,  45,  21,  44,   2,   3,
+      4,   5,   6,   7,   8,   9,  10,  11,  28,   1,  14,  15,  57,  12,  13,  26,
+     27,  43,  43,  39,  40,  41,  51,  52,  53,  58,  59,  60,  61,  62,  63,  64,
+     65,  66,  67,  68,  87,  88,  99,  70, 119, 110, 102, 104, 111, 107, 109, 106,
+    105, 108, 103,  69,  98,  55,  74,  78,  96,  79,  80,  81,  75,  76,  77,  71,
+     72,  73,  82,  83,  86, 127, 116, 117, 183, 184, 185, 186, 187, 188, 189, 190,
+    191, 192, 193, 194, 134, 138, 130, 132, 128, 129, 131, 137, 133, 135, 136, 113,
+    115, 114,   0,   0,   0, 121,   0,  89,  93, 124,  92,  94,  95,   0,   0,   0,
+    122, 123,  90,  91,  85,   0,   0,   0,   0,   0,   0,   0, 111,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0, 179, 180,   0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0, 111,   0,   0,   0,   0,   0,   0,   0,
+     29,  42,  56, 125,  97,  54, 100, 126, 164, 166, 165, 163, 161, 115, 114, 113,
+    150, 158, 159, 128, 136, 177, 178, 176, 142, 152, 173, 140,   0,   0,   0,   0,
+];
+
+/// HID usages and Linux codes of the key codes past the keyboard usages. They come first when
+/// looking a Linux code up, so `KEY_MUTE` gives [`KeyCode::Mute`] rather than
+/// [`KeyCode::_MUTE`].
+const NON_KEYBOARD: [(KeyCode, (u16, u16), u16); 17] = [
+    (KeyCode::MediaLaunch, (CONSUMER_PAGE, 0x0183), 171),
+    (KeyCode::MediaStop, (CONSUMER_PAGE, 0x00B7), 166),
+    (KeyCode::MediaPrevious, (CONSUMER_PAGE, 0x00B6), 165),
+    (KeyCode::PlayPause, (CONSUMER_PAGE, 0x00CD), 164),
+    (KeyCode::MediaNext, (CONSUMER_PAGE, 0x00B5), 163),
+    (KeyCode::Mute, (CONSUMER_PAGE, 0x00E2), 113),
+    (KeyCode::VolumeDown, (CONSUMER_PAGE, 0x00EA), 114),
+    (KeyCode::VolumeUp, (CONSUMER_PAGE, 0x00E9), 115),
+    (KeyCode::MouseLeftClick, (BUTTON_PAGE, 1), 0x110),
+    (KeyCode::MouseRightClick, (BUTTON_PAGE, 2), 0x111),
+    (KeyCode::MouseMiddleClick, (BUTTON_PAGE, 3), 0x112),
+    (KeyCode::MouseBackward, (BUTTON_PAGE, 4), 0x113),
+    (KeyCode::MouseForward, (BUTTON_PAGE, 5), 0x114),
+    (KeyCode::Leftctrl, (KEYBOARD_PAGE, 0xE0), 29),
+    (KeyCode::Leftshift, (KEYBOARD_PAGE, 0xE1), 42),
+    (KeyCode::Leftalt, (KEYBOARD_PAGE, 0xE2), 56),
+    (KeyCode::Leftmeta, (KEYBOARD_PAGE, 0xE3), 125),
+];
+
+/// Other names keys go by, normalized like in [`KeyCode::from_str`].
+const ALIASES: [(&str, KeyCode); 83] = [
+    ("none", KeyCode::Disable),
+    ("disabled", KeyCode::Disable),
+    ("1", KeyCode::One),
+    ("2", KeyCode::Two),
+    ("3", KeyCode::Three),
+    ("4", KeyCode::Four),
+    ("5", KeyCode::Five),
+    ("6", KeyCode::Six),
+    ("7", KeyCode::Seven),
+    ("8", KeyCode::Eight),
+    ("9", KeyCode::Nine),
+    ("0", KeyCode::Zero),
+    ("return", KeyCode::Enter),
+    ("escape", KeyCode::Esc),
+    ("bksp", KeyCode::Backspace),
+    ("spacebar", KeyCode::Space),
+    ("-", KeyCode::Minus),
+    ("=", KeyCode::Equal),
+    ("[", KeyCode::Leftbrace),
+    ("]", KeyCode::Rightbrace),
+    ("\\", KeyCode::Backslash),
+    ("#", KeyCode::Hashtilde),
+    (";", KeyCode::Semicolon),
+    ("'", KeyCode::Apostrophe),
+    ("`", KeyCode::Grave),
+    (",", KeyCode::Comma),
+    (".", KeyCode::Dot),
+    ("period", KeyCode::Dot),
+    ("/", KeyCode::Slash),
+    ("caps", KeyCode::Capslock),
+    ("print", KeyCode::Sysrq),
+    ("printscreen", KeyCode::Sysrq),
+    ("prtsc", KeyCode::Sysrq),
+    ("scrlk", KeyCode::Scrolllock),
+    ("break", KeyCode::Pause),
+    ("ins", KeyCode::Insert),
+    ("del", KeyCode::Delete),
+    ("pgup", KeyCode::Pageup),
+    ("pgdn", KeyCode::Pagedown),
+    ("numlk", KeyCode::Numlock),
+    ("kp/", KeyCode::KPSlash),
+    ("kp*", KeyCode::KPAsterisk),
+    ("kp-", KeyCode::KPMinus),
+    ("kp+", KeyCode::KPPlus),
+    ("kp.", KeyCode::KPDot),
+    ("kp=", KeyCode::KPEqual),
+    ("menu", KeyCode::Compose),
+    ("app", KeyCode::Compose),
+    ("vol+", KeyCode::VolumeUp),
+    ("volup", KeyCode::VolumeUp),
+    ("vol-", KeyCode::VolumeDown),
+    ("voldown", KeyCode::VolumeDown),
+    ("play", KeyCode::PlayPause),
+    ("next", KeyCode::MediaNext),
+    ("nextsong", KeyCode::MediaNext),
+    ("prev", KeyCode::MediaPrevious),
+    ("previoussong", KeyCode::MediaPrevious),
+    ("stopcd", KeyCode::MediaStop),
+    ("lmb", KeyCode::MouseLeftClick),
+    ("btnleft", KeyCode::MouseLeftClick),
+    ("rmb", KeyCode::MouseRightClick),
+    ("btnright", KeyCode::MouseRightClick),
+    ("mmb", KeyCode::MouseMiddleClick),
+    ("btnmiddle", KeyCode::MouseMiddleClick),
+    ("btnside", KeyCode::MouseBackward),
+    ("btnextra", KeyCode::MouseForward),
+    ("ctrl", KeyCode::Leftctrl),
+    ("control", KeyCode::Leftctrl),
+    ("lctrl", KeyCode::Leftctrl),
+    ("shift", KeyCode::Leftshift),
+    ("lshift", KeyCode::Leftshift),
+    ("alt", KeyCode::Leftalt),
+    ("lalt", KeyCode::Leftalt),
+    ("meta", KeyCode::Leftmeta),
+    ("super", KeyCode::Leftmeta),
+    ("win", KeyCode::Leftmeta),
+    ("cmd", KeyCode::Leftmeta),
+    ("rctrl", KeyCode::Rightctrl),
+    ("rshift", KeyCode::Rightshift),
+    ("ralt", KeyCode::Rightalt),
+    ("altgr", KeyCode::Rightalt),
+    ("rmeta", KeyCode::Rightmeta),
+    ("rsuper", KeyCode::Rightmeta),
+];
+
+/// What a [`KeyCode`] makes the pad do, see [`KeyCode::kind`].
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum KeyKind {
+    /// A key sent on the boot keyboard interface
+    Keyboard,
+    /// A media key sent as a consumer control usage
+    Consumer,
+    /// A mouse button
+    Mouse,
+    /// Something the pad handles itself, like switching layers
+    PadFunction,
+    /// A code the pad takes, but that does nothing on Linux or isn't known to do anything
+    Unsupported,
+}
+
+/// A byte the pad can bind to a key.
+///
+/// Up to 0xA3 the table follows the HID keyboard page, the pad sending those bytes as the usages
+/// they stand for. The variants at 0xB0 to 0xB3, 0xC5 to 0xDD and 0xE0 to 0xF6 assume the
+/// firmware forwards the bytes it has no function of its own for the same way. That is unverified:
+/// no vendor documentation covers it and it hasn't been tried on a pad, so their
+/// [`KeyCode::kind`] is [`KeyKind::Unsupported`] until it is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, FromPrimitive)]
 #[repr(u8)]
 pub enum KeyCode {
     // https://gist.github.com/MightyPork/6da26e382a7ad91b5496ee55fdc73db2
@@ -180,8 +346,16 @@ pub enum KeyCode {
     PlayPause,         // Play/Pause
     MediaNext,         // Media Next
     Mute,              // Mute
-    VolumeDown,        // Volume Up
-    VolumeUp,          // Volume Down
+    VolumeDown,        // Volume Down
+    VolumeUp,          // Volume Up
+
+    // the firmware keeps 0xB4 to 0xC4 for its own functions, so only the start of the keypad
+    // block before them and the rest after them could be sent, if the firmware forwards them
+    // (unverified)
+    KP00 = 0xB0,        // Keypad 00
+    KP000,              // Keypad 000
+    ThousandsSeparator, // Thousands Separator
+    DecimalSeparator,   // Decimal Separator
 
     Layer1 = 0xB4, // Switch to Layer 1
     Layer2,        // Switch to Layer 2,
@@ -200,99 +374,230 @@ pub enum KeyCode {
     MouseBackward = 0xC3, // Mouse backward click
     MouseForward,         // Mouse forward click
 
+    KPLess = 0xC5,     // Keypad <
+    KPGreater,         // Keypad >
+    KPAmpersand,       // Keypad &
+    KPDoubleAmpersand, // Keypad &&
+    KPPipe,            // Keypad |
+    KPDoublePipe,      // Keypad ||
+    KPColon,           // Keypad :
+    KPHash,            // Keypad #
+    KPSpace,           // Keypad Space
+    KPAt,              // Keypad @
+    KPExclamation,     // Keypad !
+    KPMemoryStore,     // Keypad Memory Store
+    KPMemoryRecall,    // Keypad Memory Recall
+    KPMemoryClear,     // Keypad Memory Clear
+    KPMemoryAdd,       // Keypad Memory Add
+    KPMemorySubtract,  // Keypad Memory Subtract
+    KPMemoryMultiply,  // Keypad Memory Multiply
+    KPMemoryDivide,    // Keypad Memory Divide
+    KPPlusMinus,       // Keypad +/-
+    KPClear,           // Keypad Clear
+    KPClearEntry,      // Keypad Clear Entry
+    KPBinary,          // Keypad Binary
+    KPOctal,           // Keypad Octal
+    KPDecimal,         // Keypad Decimal
+    KPHexadecimal,     // Keypad Hexadecimal
+
+    // unverified, like the keypad codes above
+    _LEFTCTRL = 0xE0, // Keyboard Left Control, as a plain usage
+    _LEFTSHIFT,       // Keyboard Left Shift, as a plain usage
+    _LEFTALT,         // Keyboard Left Alt, as a plain usage
+    _LEFTMETA,        // Keyboard Left GUI, as a plain usage
+    Rightctrl,        // Keyboard Right Control
+    Rightshift,       // Keyboard Right Shift
+    Rightalt,         // Keyboard Right Alt
+    Rightmeta,        // Keyboard Right GUI (AKA Windows Key/Super Key)
+
+    // usages only Linux gives a meaning to, up to where the firmware's codes start again
+    _MEDIAPLAYPAUSE,    // Media Play/Pause
+    _MEDIASTOPCD,       // Media Stop CD
+    _MEDIAPREVIOUSSONG, // Media Previous Song
+    _MEDIANEXTSONG,     // Media Next Song
+    _MEDIAEJECTCD,      // Media Eject CD
+    _MEDIAVOLUMEUP,     // Media Volume Up
+    _MEDIAVOLUMEDOWN,   // Media Volume Down
+    _MEDIAMUTE,         // Media Mute
+    _MEDIAWWW,          // Media WWW
+    _MEDIABACK,         // Media Back
+    _MEDIAFORWARD,      // Media Forward
+    _MEDIASTOP,         // Media Stop
+    _MEDIAFIND,         // Media Find
+    _MEDIASCROLLUP,     // Media Scroll Up
+    _MEDIASCROLLDOWN,   // Media Scroll Down
+
     DoNotUseLaunchProgram = 0xF7, // Launch a program. (Doesn't work on Linux yet.)
     SetMacro,                     // Set a macro (Doesn't work on Linux yet.)
 
     LockUnlockWholeS = 0xFA, // Lock/Unlock the whole keyboard.
 
-    // _
-    // _
-    // _
-    // _
-    // _
-    // _
-    // _
-    // _
-    // _
-    // _
-    // _
-    // _ // Keypad 00
-    // _ // Keypad 000
-    // _ // Thousands Separator
-    // _ // Decimal Separator
-    // _ // Currency Unit
-    // _ // Currency Sub-unit
-    // _ // Keypad (
-    // _ // Keypad )
-    // _ // Keypad {
-    // _ // Keypad }
-    // _ // Keypad Tab
-    // _ // Keypad Backspace
-    // _ // Keypad A
-    // _ // Keypad B
-    // _ // Keypad C
-    // _ // Keypad D
-    // _ // Keypad E
-    // _ // Keypad F
-    // _ // Keypad XOR
-    // _ // Keypad ^
-    // _ // Keypad %
-    // _ // Keypad <
-    // _ // Keypad >
-    // _ // Keypad &
-    // _ // Keypad &&
-    // _ // Keypad |
-    // _ // Keypad ||
-    // _ // Keypad :
-    // _ // Keypad #
-    // _ // Keypad Space
-    // _ // Keypad @
-    // _ // Keypad !
-    // _ // Keypad Memory Store
-    // _ // Keypad Memory Recall
-    // _ // Keypad Memory Clear
-    // _ // Keypad Memory Add
-    // _ // Keypad Memory Subtract
-    // _ // Keypad Memory Multiply
-    // _ // Keypad Memory Divide
-    // _ // Keypad +/-
-    // _ // Keypad Clear
-    // _ // Keypad Clear Entry
-    // _ // Keypad Binary
-    // _ // Keypad Octal
-    // _ // Keypad Decimal
-    // _ // Keypad Hexadecimal
-    // _
-    // _
     Leftctrl,  // Keyboard Left Control
     Leftshift, // Keyboard Left Shift
     Leftalt,   // Keyboard Left Alt
     Leftmeta,  // Keyboard Left GUI (AKA Windows Key/Super Key)
-               // Rightctrl,  // Keyboard Right Control
-               // Rightshift, // Keyboard Right Shift
-               // Rightalt,   // Keyboard Right Alt
-               // Rightmeta,  // Keyboard Right GUI (AKA Windows Key/Super Key)
-
-               // _MEDIA_PLAYPAUSE
-               // _MEDIA_STOPCD
-               // _MEDIA_PREVIOUSSONG
-               // _MEDIA_NEXTSONG
-               // _MEDIA_EJECTCD
-               // _MEDIA_VOLUMEUP
-               // _MEDIA_VOLUMEDOWN
-               // _MEDIA_MUTE
-               // _MEDIA_WWW
-               // _MEDIA_BACK
-               // _MEDIA_FORWARD
-               // _MEDIA_STOP
-               // _MEDIA_FIND
-               // _MEDIA_SCROLLUP
-               // _MEDIA_SCROLLDOWN
-               // _MEDIA_EDIT
-               // _MEDIA_SLEEP
-               // _MEDIA_COFFEE
-               // _MEDIA_REFRESH
-               // _MEDIA_CALC
+}
+
+impl KeyCode {
+    /// Every key code, in byte order.
+    pub fn all() -> impl Iterator<Item = KeyCode> {
+        (0..=u8::MAX).filter_map(KeyCode::from_u8)
+    }
+
+    /// Whether the pad sends the byte as it is, as a keyboard usage, which is assumed past 0xA3.
+    fn is_keyboard_usage(&self) -> bool {
+        matches!(*self as u8, 0x01..=0xA3) || self.is_assumed_usage()
+    }
+
+    /// Whether the byte is only assumed to be sent as a keyboard usage, see [`KeyCode`].
+    pub(crate) fn is_assumed_usage(&self) -> bool {
+        matches!(*self as u8, 0xB0..=0xB3 | 0xC5..=0xDD | 0xE0..=0xF6)
+    }
+
+    /// What the key code makes the pad do.
+    pub fn kind(&self) -> KeyKind {
+        match self {
+            KeyCode::DoNotUseLaunchProgram | KeyCode::SetMacro => KeyKind::Unsupported,
+            _ if self.is_assumed_usage() => KeyKind::Unsupported,
+            _ if self.is_keyboard_usage() => match self.to_evdev() {
+                Some(_) => KeyKind::Keyboard,
+                // sent, but dropped by the kernel
+                None => KeyKind::Unsupported,
+            },
+            _ => match self.hid_usage() {
+                Some((KEYBOARD_PAGE, _)) => KeyKind::Keyboard,
+                Some((CONSUMER_PAGE, _)) => KeyKind::Consumer,
+                Some((BUTTON_PAGE, _)) => KeyKind::Mouse,
+                _ => KeyKind::PadFunction,
+            },
+        }
+    }
+
+    /// HID usage page and id the key code is sent as, if any.
+    pub fn hid_usage(&self) -> Option<(u16, u16)> {
+        if self.is_keyboard_usage() {
+            return Some((KEYBOARD_PAGE, *self as u16));
+        }
+
+        NON_KEYBOARD
+            .iter()
+            .find(|(key_code, _, _)| key_code == self)
+            .map(|(_, usage, _)| *usage)
+    }
+
+    /// The key code sent as a HID usage. Usages more than one key code sends give the one the pad
+    /// has a dedicated byte for.
+    pub fn from_hid_usage(page: u16, id: u16) -> Option<Self> {
+        if let Some((key_code, _, _)) = NON_KEYBOARD
+            .iter()
+            .find(|(_, usage, _)| *usage == (page, id))
+        {
+            return Some(*key_code);
+        }
+
+        match page {
+            KEYBOARD_PAGE => Self::from_u16(id).filter(Self::is_keyboard_usage),
+            _ => None,
+        }
+    }
+
+    /// Linux input event code (`KEY_*` or `BTN_*`) of the key code, if any.
+    pub fn to_evdev(&self) -> Option<u16> {
+        let code = if self.is_keyboard_usage() {
+            EVDEV_KEYBOARD[*self as usize]
+        } else {
+            NON_KEYBOARD
+                .iter()
+                .find(|(key_code, _, _)| key_code == self)?
+                .2
+        };
+
+        (code != 0).then_some(code)
+    }
+
+    /// The key code sending a Linux input event code. Codes more than one key code sends give
+    /// the one the pad has a dedicated byte for, else the lowest.
+    pub fn from_evdev(code: u16) -> Option<Self> {
+        if code == 0 {
+            return None;
+        }
+
+        if let Some((key_code, _, _)) = NON_KEYBOARD.iter().find(|(_, _, evdev)| *evdev == code) {
+            return Some(*key_code);
+        }
+
+        Self::all().find(|key_code| {
+            key_code.is_keyboard_usage() && EVDEV_KEYBOARD[*key_code as usize] == code
+        })
+    }
+}
+
+/// Lowercases a key name, dropping a `KEY_` prefix, underscores and spaces.
+fn normalize(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    let name = name.strip_prefix("key_").unwrap_or(&name);
+    name.chars().filter(|c| *c != '_' && *c != ' ').collect()
+}
+
+impl FromStr for KeyCode {
+    type Err = Error;
+
+    /// Parses a key code by its variant name, case-insensitively, by a Linux `KEY_*` name, or by
+    /// a common alias like `ctrl`, `vol+` or `kp_*`.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Some(key_code) =
+            Self::all().find(|key_code| format!("{key_code:?}").eq_ignore_ascii_case(s))
+        {
+            return Ok(key_code);
+        }
+
+        let name = normalize(s);
+        if let Some((_, key_code)) = ALIASES.iter().find(|(alias, _)| *alias == name) {
+            return Ok(*key_code);
+        }
+
+        // the codes only meant as placeholders, named with a leading underscore, come last
+        let (placeholders, named): (Vec<_>, Vec<_>) =
+            Self::all().partition(|key_code| format!("{key_code:?}").starts_with('_'));
+        named
+            .into_iter()
+            .chain(placeholders)
+            .find(|key_code| !name.is_empty() && normalize(&format!("{key_code:?}")) == name)
+            .ok_or_else(|| Error::invalid_config("key_code", format!("unknown key {s:?}")))
+    }
+}
+
+impl TryFrom<u8> for KeyCode {
+    type Error = Error;
+
+    /// Takes a byte as the pad stores it, rejecting the bytes that aren't key codes.
+    fn try_from(byte: u8) -> Result<Self> {
+        Self::from_u8(byte).ok_or_else(|| {
+            Error::invalid_config("key_code", format!("0x{byte:02X} is not a key code"))
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for KeyCode {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{self:?}"))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for KeyCode {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
 }
 
 impl std::fmt::Display for KeyCode {
@@ -1,9 +1,17 @@
 use num_derive::FromPrimitive;
-use rusb::{Result, UsbContext};
+use num_traits::FromPrimitive as _;
 
-use crate::{keycode::KeyCode, Falcon8, Mode, Report};
+use crate::{
+    keycode::KeyCode, r#macro::macro_frames, Binding, Color, Falcon8, Macro, Mode, Modifiers,
+    Report, Result, Transport,
+};
+
+/// Offset of the per-key macro slots, laid out like the key codes
+const MACRO_SLOTS: usize = 0x0A;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, FromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Key {
     One,
     Two,
@@ -16,17 +24,105 @@ pub enum Key {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KeyControl {
     pub key: Key,
     pub key_code: KeyCode,
+    /// Modifiers held along with `key_code`, which make the key play a macro
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub modifiers: Modifiers,
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KeyControls {
     pub keys: [KeyControl; 8],
+    /// Keys rebound from a shortcut to a key code, whose macro slot the next update clears
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) dropped_shortcuts: [bool; 8],
 }
 
 impl Key {
+    pub const ALL: [Key; 8] = [
+        Key::One,
+        Key::Two,
+        Key::Three,
+        Key::Four,
+        Key::Five,
+        Key::Six,
+        Key::Seven,
+        Key::Eight,
+    ];
+
+    /// Rows of keys on the pad
+    pub const ROWS: usize = 2;
+    /// Keys in every row of the pad
+    pub const COLUMNS: usize = 4;
+
+    /// Row and column of the key, counting from the top left with the cable facing away. The top
+    /// row holds [`Key::One`] to [`Key::Four`].
+    pub fn position(&self) -> (usize, usize) {
+        (
+            *self as usize / Self::COLUMNS,
+            *self as usize % Self::COLUMNS,
+        )
+    }
+
+    /// The key at `row` and `column`, if the pad has one there.
+    pub fn at(row: usize, column: usize) -> Option<Self> {
+        if row >= Self::ROWS || column >= Self::COLUMNS {
+            return None;
+        }
+
+        Self::from_usize(row * Self::COLUMNS + column)
+    }
+
+    /// The keys directly above, below, left and right of this one, in that order.
+    pub fn neighbours(&self) -> impl Iterator<Item = Key> {
+        let (row, column) = self.position();
+        [
+            row.checked_sub(1).map(|row| (row, column)),
+            Some((row + 1, column)),
+            column.checked_sub(1).map(|column| (row, column)),
+            Some((row, column + 1)),
+        ]
+        .into_iter()
+        .flatten()
+        .filter_map(|(row, column)| Self::at(row, column))
+    }
+
+    /// The keys of `row` from left to right, none past the last row.
+    pub fn row(row: usize) -> impl Iterator<Item = Key> {
+        (0..Self::COLUMNS).filter_map(move |column| Self::at(row, column))
+    }
+
+    /// The keys of `column` from top to bottom, none past the last column.
+    pub fn column(column: usize) -> impl Iterator<Item = Key> {
+        (0..Self::ROWS).filter_map(move |row| Self::at(row, column))
+    }
+
+    /// The key found in this one's place once the pad is turned upside down, e.g. to light up
+    /// what a user sees as the top left key of a pad mounted that way. Rotating twice gives back
+    /// the same key.
+    pub fn rotated(&self) -> Self {
+        let (row, column) = self.position();
+        Self::at(Self::ROWS - 1 - row, Self::COLUMNS - 1 - column).unwrap()
+    }
+
+    /// Name of the key as used for report fields, e.g. `key_one`
+    pub fn field_name(&self) -> &'static str {
+        match self {
+            Key::One => "key_one",
+            Key::Two => "key_two",
+            Key::Three => "key_three",
+            Key::Four => "key_four",
+            Key::Five => "key_five",
+            Key::Six => "key_six",
+            Key::Seven => "key_seven",
+            Key::Eight => "key_eight",
+        }
+    }
+
     pub fn to_index(&self) -> usize {
         match self {
             Key::One => 0x08,
@@ -67,6 +163,11 @@ impl Key {
         }
     }
 
+    /// Number of the firmware macro slot owned by this key, starting at 1 since 0 means no macro
+    pub fn macro_slot(&self) -> u8 {
+        *self as u8 + 1
+    }
+
     pub fn to_macro_index(&self) -> usize {
         match self {
             Key::One => 0x00,
@@ -88,41 +189,71 @@ impl KeyControls {
                 KeyControl {
                     key: Key::One,
                     key_code: KeyCode::Mute,
+                    modifiers: Modifiers::NONE,
                 },
                 KeyControl {
                     key: Key::Two,
                     key_code: KeyCode::PlayPause,
+                    modifiers: Modifiers::NONE,
                 },
                 KeyControl {
                     key: Key::Three,
                     key_code: KeyCode::MediaStop,
+                    modifiers: Modifiers::NONE,
                 },
                 KeyControl {
                     key: Key::Four,
                     key_code: KeyCode::VolumeUp,
+                    modifiers: Modifiers::NONE,
                 },
                 KeyControl {
                     key: Key::Five,
                     key_code: KeyCode::MediaLaunch,
+                    modifiers: Modifiers::NONE,
                 },
                 KeyControl {
                     key: Key::Six,
                     key_code: KeyCode::MediaPrevious,
+                    modifiers: Modifiers::NONE,
                 },
                 KeyControl {
                     key: Key::Seven,
                     key_code: KeyCode::MediaNext,
+                    modifiers: Modifiers::NONE,
                 },
                 KeyControl {
                     key: Key::Eight,
                     key_code: KeyCode::VolumeDown,
+                    modifiers: Modifiers::NONE,
                 },
             ],
+            dropped_shortcuts: [false; 8],
         }
     }
 
     pub fn set_key(&mut self, key: Key, key_code: KeyCode) {
-        self.keys[key as usize].key_code = key_code;
+        self.set_binding(key, key_code);
+    }
+
+    /// Binds `key` to a key code or a shortcut like Ctrl+Shift+T.
+    pub fn set_binding(&mut self, key: Key, binding: impl Into<Binding>) {
+        let binding = binding.into();
+        let control = &mut self.keys[key as usize];
+        let was_shortcut = control.binding().key_code().is_none();
+        let dropped = &mut self.dropped_shortcuts[key as usize];
+        *dropped = binding.key_code().is_some() && (was_shortcut || *dropped);
+        control.key_code = binding.key_code;
+        control.modifiers = binding.modifiers;
+    }
+
+    pub fn binding(&self, key: Key) -> Binding {
+        self.keys[key as usize].binding()
+    }
+}
+
+impl KeyControl {
+    pub fn binding(&self) -> Binding {
+        Binding::new(self.modifiers, self.key_code)
     }
 }
 
@@ -134,75 +265,65 @@ impl Default for KeyControls {
 
 impl Report {
     pub fn set_key(&mut self, key: Key, key_code: KeyCode) {
-        match key {
-            Key::One => self.data_mut().key_one = key_code,
-            Key::Two => self.data_mut().key_two = key_code,
-            Key::Three => self.data_mut().key_three = key_code,
-            Key::Four => self.data_mut().key_four = key_code,
-            Key::Five => self.data_mut().key_five = key_code,
-            Key::Six => self.data_mut().key_six = key_code,
-            Key::Seven => self.data_mut().key_seven = key_code,
-            Key::Eight => self.data_mut().key_eight = key_code,
-        }
+        self[key.to_index()] = key_code as u8;
     }
 
-    pub fn set_key_color(&mut self, key: Key, color: (u8, u8, u8)) {
-        match key {
-            Key::One => {
-                self.data_mut().key_one_red = color.0;
-                self.data_mut().key_one_green = color.1;
-                self.data_mut().key_one_blue = color.2;
-            }
-            Key::Two => {
-                self.data_mut().key_two_red = color.0;
-                self.data_mut().key_two_green = color.1;
-                self.data_mut().key_two_blue = color.2;
-            }
-            Key::Three => {
-                self.data_mut().key_three_red = color.0;
-                self.data_mut().key_three_green = color.1;
-                self.data_mut().key_three_blue = color.2;
-            }
-            Key::Four => {
-                self.data_mut().key_four_red = color.0;
-                self.data_mut().key_four_green = color.1;
-                self.data_mut().key_four_blue = color.2;
-            }
-            Key::Five => {
-                self.data_mut().key_five_red = color.0;
-                self.data_mut().key_five_green = color.1;
-                self.data_mut().key_five_blue = color.2;
-            }
-            Key::Six => {
-                self.data_mut().key_six_red = color.0;
-                self.data_mut().key_six_green = color.1;
-                self.data_mut().key_six_blue = color.2;
-            }
-            Key::Seven => {
-                self.data_mut().key_seven_red = color.0;
-                self.data_mut().key_seven_green = color.1;
-                self.data_mut().key_seven_blue = color.2;
-            }
-            Key::Eight => {
-                self.data_mut().key_eight_red = color.0;
-                self.data_mut().key_eight_green = color.1;
-                self.data_mut().key_eight_blue = color.2;
-            }
-        }
+    pub fn set_key_color(&mut self, key: Key, color: impl Into<Color>) {
+        let color = color.into();
+        let (red, green, blue) = key.to_color_indices();
+        self[red] = color.red;
+        self[green] = color.green;
+        self[blue] = color.blue;
     }
 
-    // pub fn set_macro(report: &mut Report, key: Key, macro_index: u8) {
-    //     let index = key.to_macro_index();
-    //     report[index] = macro_index;
-    // }
+    /// Points the key at a macro slot, 0 meaning the key has no macro
+    pub fn set_macro(&mut self, key: Key, macro_index: u8) {
+        self[MACRO_SLOTS + key.to_macro_index()] = macro_index;
+    }
+
+    /// The macro slot the key points at, 0 meaning the key has no macro
+    pub fn macro_slot(&self, key: Key) -> u8 {
+        self[MACRO_SLOTS + key.to_macro_index()]
+    }
 }
 
-impl<T: UsbContext> Falcon8<T> {
+impl<T: Transport> Falcon8<T> {
+    /// Writes the key bindings to `report`, pointing the keys bound to shortcuts at their macro
+    /// slot. The macros those keys need are returned, to be uploaded along with the report.
+    ///
+    /// The other slots are left as the pad has them, so macros uploaded on their own keep
+    /// playing, except for keys rebound from a shortcut with no macro of their own in
+    /// [`Falcon8::macro_controls`].
+    pub(crate) fn set_keys_in_report(&self, report: &mut Report) -> Vec<(Key, Macro)> {
+        let mut macros = Vec::new();
+
+        for key_control in self.key_controls.keys.iter() {
+            let binding = key_control.binding();
+            match binding.key_code() {
+                Some(KeyCode::Disable) => continue, // TODO: do we want to allow disabling?
+                Some(key_code) => {
+                    report.set_key(key_control.key, key_code);
+
+                    // the shortcut's macro left in the slot would play instead of the key code
+                    let key = key_control.key as usize;
+                    if self.key_controls.dropped_shortcuts[key]
+                        && self.macro_controls.macros[key].is_none()
+                    {
+                        report.set_macro(key_control.key, 0);
+                    }
+                }
+                None => {
+                    report.set_macro(key_control.key, key_control.key.macro_slot());
+                    macros.push((key_control.key, binding.to_macro()));
+                }
+            }
+        }
+
+        macros
+    }
+
     pub fn get_keys(&mut self) -> Result<Report> {
-        let mut report = Report::new();
-        report.data_mut().zeroth_byte = 0x07;
-        report.data_mut().mode = Mode::KeyRead;
-        report.data_mut().active_layer = self.active_layer;
+        let mut report = Report::from_falcon(self);
 
         self.set_report(&report)?;
         self.get_report(&mut report)?;
@@ -210,30 +331,24 @@ impl<T: UsbContext> Falcon8<T> {
         Ok(report)
     }
 
-    pub fn update_keys(&mut self) -> Result<()> {
-        let mut report = Report::new();
-        report.data_mut().zeroth_byte = 0x07;
-        report.data_mut().mode = Mode::KeyRead;
-        report.data_mut().active_layer = self.active_layer;
-
-        self.set_report(&report)?;
+    /// The reports [`Falcon8::update_keys`] sends after reading the layer's `current` report:
+    /// the key write, the frames of the macros shortcuts need, and the commit.
+    pub(crate) fn key_update_reports(&self, mut current: Report) -> Result<Vec<Report>> {
+        current.set_mode(Mode::KeyWrite).clear_end();
+        let macros = self.set_keys_in_report(&mut current);
 
-        self.get_report(&mut report)?;
+        let mut reports = vec![current];
+        reports.extend(macro_frames(self.active_layer, &macros)?);
+        reports.push(Report::finalize());
+        Ok(reports)
+    }
 
-        report.set_mode(Mode::KeyWrite).clear_end();
-        for key_control in self.key_controls.keys.iter() {
-            if key_control.key_code == KeyCode::Disable {
-                continue; // TODO: do we want to allow disabling?
-            }
-            report.set_key(key_control.key, key_control.key_code);
+    pub fn update_keys(&mut self) -> Result<()> {
+        let current = self.get_keys()?;
+        for report in self.key_update_reports(current)? {
+            self.set_report(&report)?;
         }
-
-        self.set_report(&report)?;
-
-        report.fill(0);
-        report.data_mut().zeroth_byte = 0x07;
-        report.data_mut().mode = Mode::Finalize;
-        self.set_report(&report)?;
+        self.key_controls.dropped_shortcuts = [false; 8];
 
         Ok(())
     }
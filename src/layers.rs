@@ -1,9 +1,10 @@
 use num_derive::FromPrimitive;
-use rusb::{Result, UsbContext};
 
-use crate::{Falcon8, Mode, Report};
+use crate::{Falcon8, Mode, Report, Result, Transport};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, FromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Layer {
     One = 1,
     Two,
@@ -12,13 +13,28 @@ pub enum Layer {
     Five,
 }
 
-impl<T: UsbContext> Falcon8<T> {
+impl Layer {
+    pub const ALL: [Layer; 5] = [
+        Layer::One,
+        Layer::Two,
+        Layer::Three,
+        Layer::Four,
+        Layer::Five,
+    ];
+}
+
+impl Report {
+    /// The report switching the pad to `layer`.
+    pub(crate) fn layer_write(layer: Layer) -> Self {
+        let mut report = Report::empty().clear(Some(layer));
+        report.set_mode(Mode::LayerWrite);
+        report
+    }
+}
+
+impl<T: Transport> Falcon8<T> {
     pub fn update_layer(&mut self, layer: Layer) -> Result<()> {
-        let mut report = Report::new();
-        report.data_mut().zeroth_byte = 0x07;
-        report.data_mut().mode = Mode::LayerWrite;
-        report.data_mut().active_layer = layer;
         self.active_layer = layer;
-        self.set_report(&report)
+        self.set_report(&Report::layer_write(layer))
     }
 }
@@ -0,0 +1,338 @@
+use std::str::FromStr;
+
+use crate::{
+    Binding, Error, KeyCode, KeyPress, Macro, MacroData, Modifiers, Repetition, Result, MAX_INPUTS,
+};
+
+/// The printable keys of an ISO keyboard, row by row, in the order the layout tables below list
+/// their characters.
+#[rustfmt::skip]
+const KEYS: [KeyCode; 49] = [
+    KeyCode::Grave, KeyCode::One, KeyCode::Two, KeyCode::Three, KeyCode::Four, KeyCode::Five,
+    KeyCode::Six, KeyCode::Seven, KeyCode::Eight, KeyCode::Nine, KeyCode::Zero, KeyCode::Minus,
+    KeyCode::Equal,
+    KeyCode::Q, KeyCode::W, KeyCode::E, KeyCode::R, KeyCode::T, KeyCode::Y, KeyCode::U,
+    KeyCode::I, KeyCode::O, KeyCode::P, KeyCode::Leftbrace, KeyCode::Rightbrace,
+    KeyCode::Backslash,
+    KeyCode::A, KeyCode::S, KeyCode::D, KeyCode::F, KeyCode::G, KeyCode::H, KeyCode::J,
+    KeyCode::K, KeyCode::L, KeyCode::Semicolon, KeyCode::Apostrophe, KeyCode::Hashtilde,
+    KeyCode::_102ND, KeyCode::Z, KeyCode::X, KeyCode::C, KeyCode::V, KeyCode::B, KeyCode::N,
+    KeyCode::M, KeyCode::Comma, KeyCode::Dot, KeyCode::Slash,
+];
+
+/// What a layout types on each of [`KEYS`] alone, with Shift and with AltGr, `\0` where it types
+/// nothing, along with the characters that are dead keys.
+struct Keymap {
+    plain: &'static str,
+    shift: &'static str,
+    altgr: &'static str,
+    dead: &'static str,
+}
+
+const NOTHING: &str = "\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0";
+
+const US: Keymap = Keymap {
+    plain: "`1234567890-=qwertyuiop[]\\asdfghjkl;'\0\0zxcvbnm,./",
+    shift: "~!@#$%^&*()_+QWERTYUIOP{}|ASDFGHJKL:\"\0\0ZXCVBNM<>?",
+    altgr: NOTHING,
+    dead: "",
+};
+
+const UK: Keymap = Keymap {
+    plain: "`1234567890-=qwertyuiop[]\0asdfghjkl;'#\\zxcvbnm,./",
+    shift: "¬!\"£$%^&*()_+QWERTYUIOP{}\0ASDFGHJKL:@~|ZXCVBNM<>?",
+    altgr: "¦\0\0\0€\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
+    dead: "",
+};
+
+const DE: Keymap = Keymap {
+    plain: "^1234567890ß´qwertzuiopü+\0asdfghjklöä#<yxcvbnm,.-",
+    shift: "°!\"§$%&/()=?`QWERTZUIOPÜ*\0ASDFGHJKLÖÄ'>YXCVBNM;:_",
+    altgr:
+        "\0\0²³\0\0\0{[]}\\\0@\0€\0\0\0\0\0\0\0\0~\0\0\0\0\0\0\0\0\0\0\0\0\0|\0\0\0\0\0\0µ\0\0\0",
+    dead: "^´`",
+};
+
+const FR: Keymap = Keymap {
+    plain: "²&é\"'(-è_çà)=azertyuiop^$\0qsdfghjklmù*<wxcvbn,;:!",
+    shift: "\x001234567890°+AZERTYUIOP¨£\0QSDFGHJKLM%µ>WXCVBN?./§",
+    altgr: "\0\0~#{[|`\\^@]}\0\0€\0\0\0\0\0\0\0\0¤\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
+    dead: "^¨",
+};
+
+/// Accented letters typed with a dead key followed by the letter.
+#[rustfmt::skip]
+const COMPOSED: [(char, char, char); 43] = [
+    ('^', 'a', 'â'), ('^', 'e', 'ê'), ('^', 'i', 'î'), ('^', 'o', 'ô'), ('^', 'u', 'û'),
+    ('^', 'A', 'Â'), ('^', 'E', 'Ê'), ('^', 'I', 'Î'), ('^', 'O', 'Ô'), ('^', 'U', 'Û'),
+    ('´', 'a', 'á'), ('´', 'e', 'é'), ('´', 'i', 'í'), ('´', 'o', 'ó'), ('´', 'u', 'ú'),
+    ('´', 'A', 'Á'), ('´', 'E', 'É'), ('´', 'I', 'Í'), ('´', 'O', 'Ó'), ('´', 'U', 'Ú'),
+    ('´', 'y', 'ý'), ('´', 'Y', 'Ý'),
+    ('`', 'a', 'à'), ('`', 'e', 'è'), ('`', 'i', 'ì'), ('`', 'o', 'ò'), ('`', 'u', 'ù'),
+    ('`', 'A', 'À'), ('`', 'E', 'È'), ('`', 'I', 'Ì'), ('`', 'O', 'Ò'), ('`', 'U', 'Ù'),
+    ('¨', 'a', 'ä'), ('¨', 'e', 'ë'), ('¨', 'i', 'ï'), ('¨', 'o', 'ö'), ('¨', 'u', 'ü'),
+    ('¨', 'A', 'Ä'), ('¨', 'E', 'Ë'), ('¨', 'I', 'Ï'), ('¨', 'O', 'Ö'), ('¨', 'U', 'Ü'),
+    ('¨', 'y', 'ÿ'),
+];
+
+/// A keyboard layout the host interprets key codes with, as the Linux (XKB) layouts of the same
+/// name have it.
+///
+/// The pad sends key positions rather than characters, so typing text means knowing which keys
+/// the host turns into which characters. Layouts parse from `us`, `uk` (or `gb`), `de` and `fr`.
+///
+/// Characters typed with AltGr, like `€`, or `@` on German layouts, count as untypeable for now:
+/// AltGr would be sent as [`KeyCode::Rightalt`], which the pad isn't known to forward (see
+/// [`KeyCode`]).
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum Layout {
+    /// US QWERTY
+    #[default]
+    Us,
+    /// UK QWERTY
+    Uk,
+    /// German QWERTZ, with dead `^`, `´` and `` ` ``
+    De,
+    /// French AZERTY, with dead `^` and `¨`
+    Fr,
+}
+
+impl Layout {
+    pub const ALL: [Layout; 4] = [Layout::Us, Layout::Uk, Layout::De, Layout::Fr];
+
+    fn keymap(&self) -> &'static Keymap {
+        match self {
+            Layout::Us => &US,
+            Layout::Uk => &UK,
+            Layout::De => &DE,
+            Layout::Fr => &FR,
+        }
+    }
+
+    /// The key a character is on, and the modifiers picking it.
+    fn key(&self, c: char) -> Option<Binding> {
+        let keymap = self.keymap();
+        [
+            (keymap.plain, Modifiers::NONE),
+            (keymap.shift, Modifiers::SHIFT),
+            (keymap.altgr, Modifiers::RIGHT_ALT),
+        ]
+        .into_iter()
+        // AltGr is sent as KeyCode::Rightalt, whose byte isn't verified yet
+        .filter(|(_, modifiers)| {
+            modifiers
+                .key_codes()
+                .all(|key_code| !key_code.is_assumed_usage())
+        })
+        .find_map(|(chars, modifiers)| {
+            let index = chars.chars().position(|key| key == c)?;
+            Some(Binding::new(modifiers, KEYS[index]))
+        })
+    }
+
+    /// The keys to press one after the other to type `c`, if the layout can type it.
+    pub fn keystrokes(&self, c: char) -> Option<Vec<Binding>> {
+        let dead = |c| self.keymap().dead.contains(c);
+
+        match c {
+            '\0' => None,
+            '\n' => Some(vec![KeyCode::Enter.into()]),
+            '\t' => Some(vec![KeyCode::Tab.into()]),
+            ' ' => Some(vec![KeyCode::Space.into()]),
+            // a dead key only types itself when followed by a space
+            c if dead(c) => Some(vec![self.key(c)?, KeyCode::Space.into()]),
+            c => self.key(c).map(|key| vec![key]).or_else(|| {
+                let (accent, letter, _) = COMPOSED
+                    .iter()
+                    .find(|(accent, _, composed)| *composed == c && dead(*accent))?;
+                Some(vec![self.key(*accent)?, self.key(*letter)?])
+            }),
+        }
+    }
+
+    /// The keys typing `c` through the Ctrl+Shift+U input method of GTK and IBus: the code point
+    /// in hex, confirmed with a space.
+    pub fn unicode_keystrokes(&self, c: char) -> Vec<Binding> {
+        let mut keys = vec![Binding::new(Modifiers::CTRL | Modifiers::SHIFT, KeyCode::U)];
+        for digit in format!("{:x}", c as u32).chars() {
+            keys.extend(
+                self.keystrokes(digit)
+                    .expect("every layout types hex digits"),
+            );
+        }
+        keys.push(KeyCode::Space.into());
+        keys
+    }
+
+    /// How many characters from the start of `text` fit in one macro made by
+    /// [`Macro::from_unicode`].
+    pub fn unicode_capacity(&self, text: &str) -> usize {
+        capacity(&typing(&self.unicode_text(text)))
+    }
+
+    /// The keys typing each character of `text`, through Ctrl+Shift+U when the layout lacks it.
+    fn unicode_text(&self, text: &str) -> Vec<Vec<Binding>> {
+        lines(text)
+            .chars()
+            .map(|c| {
+                self.keystrokes(c)
+                    .unwrap_or_else(|| self.unicode_keystrokes(c))
+            })
+            .collect()
+    }
+}
+
+/// `text` with `\r\n` line endings turned into `\n`, so they type a single Enter.
+fn lines(text: &str) -> String {
+    text.replace("\r\n", "\n")
+}
+
+/// The inputs typing each character from its keys, and the modifiers left held after it. Shift
+/// and AltGr stay held across characters needing them.
+fn typing(characters: &[Vec<Binding>]) -> Vec<(Vec<MacroData>, Modifiers)> {
+    let input = |key_press, key_code| MacroData {
+        key_press,
+        delay: 0,
+        key_code,
+    };
+    let mut held = Modifiers::NONE;
+
+    characters
+        .iter()
+        .map(|keys| {
+            let mut inputs = Vec::new();
+            for key in keys {
+                let release = Modifiers::from_bits(held.bits() & !key.modifiers.bits());
+                let press = Modifiers::from_bits(key.modifiers.bits() & !held.bits());
+                held = key.modifiers;
+
+                inputs.extend(
+                    release
+                        .key_codes()
+                        .rev()
+                        .map(|key_code| input(KeyPress::Up, key_code)),
+                );
+                inputs.extend(
+                    press
+                        .key_codes()
+                        .map(|key_code| input(KeyPress::Down, key_code)),
+                );
+                inputs.push(input(KeyPress::Down, key.key_code));
+                inputs.push(input(KeyPress::Up, key.key_code));
+            }
+            (inputs, held)
+        })
+        .collect()
+}
+
+/// How many of the typed characters fit in a macro, counting the release of the modifiers still
+/// held after the last of them.
+fn capacity(typed: &[(Vec<MacroData>, Modifiers)]) -> usize {
+    let mut used = 0;
+    typed
+        .iter()
+        .take_while(|(inputs, held)| {
+            used += inputs.len();
+            used + held.key_codes().count() <= MAX_INPUTS
+        })
+        .count()
+}
+
+/// A macro playing the typed characters, then releasing what is still held.
+fn typing_macro(typed: Vec<(Vec<MacroData>, Modifiers)>) -> Result<Macro> {
+    let fit = capacity(&typed);
+    if fit < typed.len() {
+        return Err(Error::invalid_config(
+            "text",
+            format!("only the first {fit} characters fit in a macro's {MAX_INPUTS} inputs"),
+        ));
+    }
+
+    let mut r#macro = Macro::new(Repetition::UntilNextKeyPressed);
+    let held = typed.last().map_or(Modifiers::NONE, |(_, held)| *held);
+    for (inputs, _) in typed {
+        for data in inputs {
+            r#macro.add_macro_data(data)?;
+        }
+    }
+    for key_code in held.key_codes().rev() {
+        r#macro.add_macro_data(MacroData {
+            key_press: KeyPress::Up,
+            delay: 0,
+            key_code,
+        })?;
+    }
+
+    Ok(r#macro)
+}
+
+impl FromStr for Layout {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "us" => Ok(Layout::Us),
+            "uk" | "gb" => Ok(Layout::Uk),
+            "de" => Ok(Layout::De),
+            "fr" => Ok(Layout::Fr),
+            _ => Err(Error::invalid_config(
+                "layout",
+                format!("{s:?} is not one of us, uk, de or fr"),
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Layout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Layout::Us => write!(f, "us"),
+            Layout::Uk => write!(f, "uk"),
+            Layout::De => write!(f, "de"),
+            Layout::Fr => write!(f, "fr"),
+        }
+    }
+}
+
+impl Macro {
+    /// A macro typing `text` on a host using `layout`. It uses
+    /// [`Repetition::UntilNextKeyPressed`], the profile default.
+    ///
+    /// Shift and AltGr stay held across characters needing them, so runs of capitals take two
+    /// inputs a character. Fails listing the characters the layout can't type, AltGr ones included
+    /// (see [`Layout`]), or with how much of the text fits when it needs more than [`MAX_INPUTS`]
+    /// inputs.
+    pub fn from_text(text: &str, layout: Layout) -> Result<Macro> {
+        let mut untypeable = Vec::new();
+        let mut characters = Vec::new();
+        for c in lines(text).chars() {
+            match layout.keystrokes(c) {
+                Some(keys) => characters.push(keys),
+                None if untypeable.contains(&c) => {}
+                None => untypeable.push(c),
+            }
+        }
+
+        if !untypeable.is_empty() {
+            let untypeable = untypeable
+                .iter()
+                .map(|c| format!("{c:?} (U+{:04X})", *c as u32))
+                .collect::<Vec<_>>();
+            return Err(Error::invalid_config(
+                "text",
+                format!("the {layout} layout can't type {}", untypeable.join(", ")),
+            ));
+        }
+
+        typing_macro(typing(&characters))
+    }
+
+    /// A macro typing `text` like [`Macro::from_text`], entering the characters `layout`
+    /// lacks, like `→` or emoji, through the Ctrl+Shift+U input method of GTK and IBus.
+    ///
+    /// Every such character takes a dozen inputs or more, see [`Layout::unicode_capacity`] for how
+    /// many fit in a macro.
+    pub fn from_unicode(text: &str, layout: Layout) -> Result<Macro> {
+        typing_macro(typing(&layout.unicode_text(text)))
+    }
+}
@@ -1,10 +1,11 @@
-use num_traits::FromPrimitive;
-use rusb::{Result, UsbContext};
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive as _;
 
-use crate::{Falcon8, Key, Mode, Report};
+use crate::{Color, Error, Falcon8, Key, Mode, Report, Result, Transport};
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromPrimitive)]
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LEDMode {
     Static,
     Breathing,
@@ -16,8 +17,9 @@ pub enum LEDMode {
     Custom,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromPrimitive)]
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Brightness {
     Off,
     Low,
@@ -26,8 +28,9 @@ pub enum Brightness {
     Max,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromPrimitive)]
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Flow {
     RightToLeft,
     LeftToRight,
@@ -37,6 +40,7 @@ pub enum Flow {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LEDControls {
     pub mode: Option<LEDMode>,
     pub brightness: Option<Brightness>,
@@ -72,17 +76,16 @@ impl LEDControls {
     }
 
     /// Set the overall color of the LEDs, works with [`LEDMode::Static`], [`LEDMode::Breathing`], [`LEDMode::FadeIn`], [`LEDMode::FadeOut`], and [`LEDMode::LastKeystroke`].
-    pub fn set_color(&mut self, color: (u8, u8, u8)) -> &mut Self {
-        self.color = [color.0, color.1, color.2];
+    pub fn set_color(&mut self, color: impl Into<Color>) -> &mut Self {
+        self.color = color.into().into();
         self
     }
 
     /// Set the color of a specific key, works with [`LEDMode::Custom`].
-    pub fn set_key_color(&mut self, key: Key, color: (u8, u8, u8)) -> &mut Self {
+    pub fn set_key_color(&mut self, key: Key, color: impl Into<Color>) -> &mut Self {
         let idx = key as usize * 3;
-        self.key_colors[idx] = color.0;
-        self.key_colors[idx + 1] = color.1;
-        self.key_colors[idx + 2] = color.2;
+        let color: [u8; 3] = color.into().into();
+        self.key_colors[idx..idx + 3].copy_from_slice(&color);
         self
     }
 
@@ -102,44 +105,53 @@ impl Default for LEDControls {
     }
 }
 
-impl<T: UsbContext> Falcon8<T> {
-    fn set_leds_in_report(&mut self, report: &mut Report) -> Result<()> {
+impl<T: Transport> Falcon8<T> {
+    pub(crate) fn set_leds_in_report(&self, report: &mut Report) -> Result<()> {
         // sanity checks
         // if self.color isnt zeroes and self.mode isnt custom, error
-        // TODO: custom err
         if self.led_controls.color != [0; 3] && self.led_controls.mode != Some(LEDMode::Custom) {
-            return Err(rusb::Error::InvalidParam);
+            return Err(Error::invalid_config(
+                "led_controls.color",
+                format!(
+                    "a color is set but the LED mode is {:?}, not Custom",
+                    self.led_controls.mode
+                ),
+            ));
         }
 
         // if self.key_colors isnt zeroes and self.mode isnt custom, error
         if self.led_controls.key_colors != [0; 24]
             && self.led_controls.mode != Some(LEDMode::Custom)
         {
-            return Err(rusb::Error::InvalidParam);
+            return Err(Error::invalid_config(
+                "led_controls.key_colors",
+                format!(
+                    "key colors are set but the LED mode is {:?}, not Custom",
+                    self.led_controls.mode
+                ),
+            ));
         }
 
         if let Some(mode) = self.led_controls.mode {
-            report.data_mut().led_mode = mode;
+            report.set_led_mode(mode);
         }
 
         if let Some(brightness) = self.led_controls.brightness {
-            report.data_mut().brightness = brightness;
+            report.set_brightness(brightness);
         }
 
         if let Some(flow) = self.led_controls.flow {
-            report.data_mut().flow = flow;
+            report.set_flow(flow);
         }
 
         if self.led_controls.color != [0; 3] {
-            report.data_mut().led_red = self.led_controls.color[0];
-            report.data_mut().led_green = self.led_controls.color[1];
-            report.data_mut().led_blue = self.led_controls.color[2];
+            report.set_led_color(self.led_controls.color);
         }
 
         if self.led_controls.key_colors != [0; 24] {
             for (i, rgb) in self.led_controls.key_colors.chunks_exact(3).enumerate() {
                 if rgb != [0; 3] {
-                    report.set_key_color(Key::from_u8(i as u8).unwrap(), (rgb[0], rgb[1], rgb[2]));
+                    report.set_key_color(Key::from_u8(i as u8).unwrap(), [rgb[0], rgb[1], rgb[2]]);
                 }
             }
         }
@@ -147,18 +159,17 @@ impl<T: UsbContext> Falcon8<T> {
         Ok(())
     }
 
-    pub fn update_leds(&mut self) -> Result<()> {
-        let mut report = Report::new();
-        report.data_mut().zeroth_byte = 0x07;
-        report.data_mut().mode = Mode::KeyRead;
-        report.data_mut().active_layer = self.active_layer;
-
-        self.set_report(&report)?;
+    /// The report [`Falcon8::update_leds`] writes, given the layer's `current` one.
+    pub(crate) fn led_update_report(&self, mut current: Report) -> Result<Report> {
+        current.set_mode(Mode::KeyWrite).clear_end();
+        self.set_leds_in_report(&mut current)?;
 
-        self.get_report(&mut report)?;
+        Ok(current)
+    }
 
-        report.set_mode(Mode::KeyWrite).clear_end();
-        self.set_leds_in_report(&mut report)?;
+    pub fn update_leds(&mut self) -> Result<()> {
+        let current = self.get_keys()?;
+        let report = self.led_update_report(current)?;
         self.set_report(&report)?;
 
         Ok(())
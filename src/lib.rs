@@ -1,25 +1,67 @@
-use std::time::Duration;
-
-use rusb::{Context, Device, DeviceHandle, Direction, Recipient, RequestType, Result, UsbContext};
+use rusb::{Context, UsbContext};
 
+mod animation;
+#[cfg(feature = "async")]
+mod r#async;
+mod binding;
+mod color;
+mod config;
 mod consts;
+mod error;
+mod events;
+#[cfg(target_os = "linux")]
+mod hidraw;
+mod hotplug;
+mod import;
 mod keycode;
 mod keys;
 mod layers;
+mod layout;
 mod led;
 mod r#macro;
 mod mode;
+#[cfg(feature = "serde")]
+pub mod profile;
+mod recorder;
 mod report;
+mod script;
+mod selector;
+mod simulator;
 mod tracing;
+mod transport;
 
+pub use animation::{
+    Animation, Clock, Effect, Frame, Gradient, ManualClock, ProgressBar, Rainbow, Ripple,
+    SystemClock,
+};
+pub use binding::{Binding, Modifiers};
+pub use color::Color;
+pub use config::{DeviceConfig, LayerConfig};
 pub use consts::*;
-pub use keycode::KeyCode;
+pub use error::{Error, Result};
+pub use events::{Events, InputDecoder, InputEvent};
+#[cfg(target_os = "linux")]
+pub use hidraw::{FeatureDevice, HidrawFile, HidrawNode, HidrawTransport};
+pub use hotplug::{EventSource, HotplugEvent, HotplugWatcher, UsbEventSource};
+pub use keycode::{KeyCode, KeyKind};
 pub use keys::{Key, KeyControl, KeyControls};
 pub use layers::Layer;
+pub use layout::Layout;
 pub use led::{Brightness, Flow, LEDControls, LEDMode};
 pub use mode::Mode;
-pub use report::Report;
+#[cfg(feature = "async")]
+pub use r#async::{AsyncFalcon8, EventStream};
+pub use r#macro::{
+    KeyPress, Macro, MacroControls, MacroData, Repetition, MACRO_FRAMES, MAX_DELAY, MAX_INPUTS,
+};
+#[cfg(target_os = "linux")]
+pub use recorder::EvdevFile;
+pub use recorder::{EvdevEvent, Recorder};
+pub use report::{DecodeError, DecodedReport, Report};
+pub use selector::{Candidate, DeviceInfo, DeviceSelector, SkipReason};
+pub use simulator::Simulator;
 pub use tracing::debug_report;
+pub use transport::{Transport, UsbTransport, REPORT_DELAY};
 
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -31,31 +73,30 @@ pub struct Endpoint {
 }
 
 #[derive(Debug)]
-pub struct Falcon8<T: UsbContext> {
-    pub device: Device<T>,
-    pub handle: DeviceHandle<T>,
-    pub interfaces: Vec<u8>,
+pub struct Falcon8<T: Transport = UsbTransport<Context>> {
+    pub transport: T,
 
     pub active_layer: Layer,
     pub led_controls: LEDControls,
     pub key_controls: KeyControls,
+    pub macro_controls: MacroControls,
 }
 
-impl Falcon8<Context> {
+impl Falcon8<UsbTransport<Context>> {
     pub fn new() -> Result<Vec<Self>> {
         let mut context = Context::new()?;
         let devices = Self::open_devices(&mut context, VID, PID)?;
 
         if devices.is_empty() {
-            return Err(rusb::Error::NotFound);
+            return Err(Error::DeviceNotFound);
         }
 
         Ok(devices)
     }
 }
 
-impl<T: UsbContext> Falcon8<T> {
-    fn open_devices(context: &mut T, vid: u16, pid: u16) -> Result<Vec<Falcon8<T>>> {
+impl<T: UsbContext> Falcon8<UsbTransport<T>> {
+    fn open_devices(context: &mut T, vid: u16, pid: u16) -> Result<Vec<Self>> {
         let devices = context.devices()?;
         let mut result = Vec::new();
 
@@ -65,18 +106,10 @@ impl<T: UsbContext> Falcon8<T> {
             };
 
             if device_desc.vendor_id() == vid && device_desc.product_id() == pid {
-                if let Ok(mut handle) = device.open() {
+                if let Ok(handle) = device.open() {
                     handle.set_auto_detach_kernel_driver(true)?;
 
-                    let mut falcon8 = Falcon8 {
-                        device,
-                        handle,
-                        interfaces: Vec::new(),
-
-                        active_layer: Layer::One,
-                        led_controls: LEDControls::default(),
-                        key_controls: KeyControls::default(),
-                    };
+                    let mut falcon8 = Falcon8::with_transport(UsbTransport::new(device, handle));
 
                     falcon8.claim_interfaces()?;
 
@@ -89,44 +122,29 @@ impl<T: UsbContext> Falcon8<T> {
     }
 
     pub fn claim_interfaces(&mut self) -> Result<()> {
-        let config_desc = self.device.config_descriptor(0)?;
-        for interface in config_desc.interfaces() {
-            for interface_desc in interface.descriptors() {
-                for endpoint_desc in interface_desc.endpoint_descriptors() {
-                    if endpoint_desc.direction() == Direction::In {
-                        self.handle
-                            .claim_interface(interface_desc.interface_number())?;
-                        self.interfaces.push(interface_desc.interface_number());
-                    }
-                }
-            }
-        }
-
-        Ok(())
+        self.transport.claim_interfaces()
     }
 
     pub fn print_device_info(&self) -> Result<()> {
-        let device_desc = self.handle.device().device_descriptor()?;
+        let handle = &self.transport.handle;
+        let device_desc = handle.device().device_descriptor()?;
         let timeout = std::time::Duration::from_secs(1);
-        let languages = self.handle.read_languages(timeout)?;
+        let languages = handle.read_languages(timeout)?;
 
-        println!(
-            "Active configuration: {}",
-            self.handle.active_configuration()?
-        );
+        println!("Active configuration: {}", handle.active_configuration()?);
 
         if !languages.is_empty() {
             let language = languages[0];
 
             println!(
                 "Manufacturer: {}",
-                self.handle
+                handle
                     .read_manufacturer_string(language, &device_desc, timeout)
                     .unwrap_or_else(|_| "Not Found".to_string())
             );
             println!(
                 "Product: {}",
-                self.handle
+                handle
                     .read_product_string(language, &device_desc, timeout)
                     .unwrap_or_else(|_| "Not Found".to_string())
             );
@@ -135,7 +153,7 @@ impl<T: UsbContext> Falcon8<T> {
     }
 
     pub fn find_readable_endpoints(&self) -> Result<Vec<Endpoint>> {
-        let config_desc = self.device.config_descriptor(0)?;
+        let config_desc = self.transport.device.config_descriptor(0)?;
         let mut endpoints = vec![];
 
         for interface in config_desc.interfaces() {
@@ -153,20 +171,45 @@ impl<T: UsbContext> Falcon8<T> {
 
         Ok(endpoints)
     }
+}
+
+impl<T: Transport> Falcon8<T> {
+    /// Wraps an already opened transport, starting out on [`Layer::One`] with default controls.
+    pub fn with_transport(transport: T) -> Self {
+        Falcon8 {
+            transport,
 
+            active_layer: Layer::One,
+            led_controls: LEDControls::default(),
+            key_controls: KeyControls::default(),
+            macro_controls: MacroControls::default(),
+        }
+    }
+
+    /// Tells the pad to commit everything written so far.
+    pub fn finalize(&mut self) -> Result<()> {
+        self.set_report(&Report::finalize())
+    }
+
+    /// Receives a report, then waits for the transport's [`Transport::report_delay`].
     pub fn get_report(&mut self, report: &mut Report) -> Result<()> {
-        self.claim_interfaces()?;
-
-        let size = self.handle.read_control(
-            rusb::request_type(Direction::In, RequestType::Class, Recipient::Interface),
-            0x01,
-            0x0307,
-            0x0002,
-            report.as_mut(),
-            Duration::from_secs(1),
-        )?;
-        assert_eq!(size, 264);
-        std::thread::sleep(Duration::from_millis(50));
+        self.receive_report(report)?;
+        std::thread::sleep(self.transport.report_delay());
+
+        Ok(())
+    }
+
+    /// Sends a report, then waits for the transport's [`Transport::report_delay`].
+    pub fn set_report(&mut self, report: &Report) -> Result<()> {
+        self.send_report(report)?;
+        std::thread::sleep(self.transport.report_delay());
+
+        Ok(())
+    }
+
+    /// [`Falcon8::get_report`] without the wait, for callers pacing reports themselves.
+    pub(crate) fn receive_report(&mut self, report: &mut Report) -> Result<()> {
+        self.transport.get_report(report)?;
 
         #[cfg(feature = "tracing")]
         debug_report(report, false);
@@ -174,19 +217,8 @@ impl<T: UsbContext> Falcon8<T> {
         Ok(())
     }
 
-    pub fn set_report(&mut self, report: &Report) -> Result<()> {
-        self.claim_interfaces()?;
-
-        let size = self.handle.write_control(
-            rusb::request_type(Direction::Out, RequestType::Class, Recipient::Interface),
-            0x09,
-            0x0307,
-            0x0002,
-            report.as_ref(),
-            Duration::from_secs(1),
-        )?;
-        assert_eq!(size, 264);
-        std::thread::sleep(Duration::from_millis(50));
+    pub(crate) fn send_report(&mut self, report: &Report) -> Result<()> {
+        self.transport.set_report(report)?;
 
         #[cfg(feature = "tracing")]
         debug_report(report, true);
@@ -207,27 +239,42 @@ mod tests {
         std::env::set_var("RUST_LOG", "debug");
     }
 
+    fn simulated() -> Falcon8<Simulator> {
+        Falcon8::with_transport(Simulator::new())
+    }
+
     #[test_log::test]
     fn test_get_report() -> Result<()> {
-        let mut falcon = Falcon8::new().unwrap().remove(0);
-        falcon.get_keys().map(|_| ())
+        let mut falcon = simulated();
+        let report = falcon.get_keys()?;
+
+        assert_eq!(
+            report.as_bytes(),
+            falcon.transport.layer(Layer::One).as_bytes()
+        );
+        assert_eq!(falcon.transport.sent_modes(), [Mode::KeyRead]);
+        Ok(())
     }
 
     #[test_log::test]
     fn test_update_layer() -> Result<()> {
-        let mut falcon = Falcon8::new().unwrap().remove(0);
+        let mut falcon = simulated();
 
         for layer in 1..=5 {
-            falcon.update_layer(Layer::from_u8(layer).unwrap())?;
-            std::thread::sleep(Duration::from_millis(500));
+            let layer = Layer::from_u8(layer).unwrap();
+            falcon.update_layer(layer)?;
+            assert_eq!(falcon.transport.active_layer(), layer);
         }
 
-        falcon.update_layer(Layer::One)
+        falcon.update_layer(Layer::One)?;
+        assert_eq!(falcon.transport.active_layer(), Layer::One);
+        assert_eq!(falcon.transport.sent_modes(), [Mode::LayerWrite; 6]);
+        Ok(())
     }
 
     #[test_log::test]
     fn test_update_leds() -> Result<()> {
-        let mut falcon = Falcon8::new().unwrap().remove(0);
+        let mut falcon = simulated();
         falcon
             .led_controls
             .set_mode(LEDMode::Static)
@@ -244,17 +291,21 @@ mod tests {
         //     .set_key_color(Key::Six, (0, 255, 255))
         //     .set_key_color(Key::Seven, (255, 255, 255))
         //     .set_key_color(Key::Eight, (0, 0, 0));
-        falcon.update_leds()
+        falcon.update_leds()?;
+
+        let layer = falcon.transport.layer(Layer::One).decode().unwrap();
+        assert_eq!(layer.led_mode, LEDMode::Static);
+        assert_eq!(layer.brightness, Brightness::Max);
+        assert_eq!(
+            falcon.transport.sent_modes(),
+            [Mode::KeyRead, Mode::KeyWrite]
+        );
+        Ok(())
     }
 
     #[test_log::test]
     fn test_update_keys() -> Result<()> {
-        let mut falcon = Falcon8::new().unwrap().remove(0);
-        let mut report = Report::new();
-        report.data_mut().zeroth_byte = 0x07;
-        report.data_mut().mode = Mode::KeyRead;
-        report.data_mut().active_layer = falcon.active_layer;
-
+        let mut falcon = simulated();
         falcon
             .led_controls
             .set_mode(LEDMode::Breathing)
@@ -267,6 +318,1973 @@ mod tests {
             .key_controls
             .set_key(Key::Seven, KeyCode::MediaPrevious);
         falcon.key_controls.set_key(Key::Six, KeyCode::MediaNext);
-        falcon.update_keys()
+        falcon.update_keys()?;
+
+        let committed = falcon.transport.committed(Layer::One).decode().unwrap();
+        assert_eq!(committed.led_mode, LEDMode::Breathing);
+        assert_eq!(committed.key_codes[Key::One as usize], KeyCode::KPAsterisk);
+        assert_eq!(committed.key_codes[Key::Five as usize], KeyCode::KPSlash);
+        assert_eq!(
+            committed.key_codes[Key::Seven as usize],
+            KeyCode::MediaPrevious
+        );
+        assert_eq!(committed.key_codes[Key::Six as usize], KeyCode::MediaNext);
+        assert_eq!(committed.key_codes[Key::Two as usize], KeyCode::PlayPause);
+        assert_eq!(
+            falcon.transport.sent_modes(),
+            [
+                Mode::KeyRead,
+                Mode::KeyWrite,
+                Mode::KeyRead,
+                Mode::KeyWrite,
+                Mode::Finalize
+            ]
+        );
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_unfinalized_writes_are_lost() -> Result<()> {
+        let mut falcon = simulated();
+        falcon.led_controls.set_mode(LEDMode::RGBWave);
+        falcon.update_leds()?;
+        assert_eq!(
+            falcon
+                .transport
+                .layer(Layer::One)
+                .decode()
+                .unwrap()
+                .led_mode,
+            LEDMode::RGBWave
+        );
+
+        falcon.transport.power_cycle();
+        assert_eq!(
+            falcon
+                .transport
+                .layer(Layer::One)
+                .decode()
+                .unwrap()
+                .led_mode,
+            LEDMode::Static
+        );
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_upload_macro() -> Result<()> {
+        let mut falcon = simulated();
+        let mut r#macro = Macro::new(Repetition::WhilePressed);
+        for key_press in [KeyPress::Down, KeyPress::Up] {
+            r#macro.add_macro_data(MacroData {
+                key_press,
+                delay: 3,
+                key_code: KeyCode::A,
+            })?;
+        }
+
+        falcon.upload_macro(Layer::Two, Key::Three, &r#macro)?;
+
+        assert_eq!(
+            falcon.transport.sent_modes(),
+            [
+                Mode::KeyRead,
+                Mode::KeyWrite,
+                Mode::MacroWrite,
+                Mode::MacroWrite,
+                Mode::MacroWrite,
+                Mode::Finalize
+            ]
+        );
+
+        let committed = falcon.transport.committed(Layer::Two);
+        assert_eq!(committed[0x0A + Key::Three.to_macro_index()], 3);
+        assert_eq!(
+            falcon.transport.committed(Layer::One),
+            falcon.transport.layer(Layer::One)
+        );
+
+        let frames = falcon.transport.macro_frames();
+        assert_eq!(frames.len(), MACRO_FRAMES);
+        for (index, frame) in frames.iter().enumerate() {
+            assert_eq!(
+                frame.as_bytes()[..8],
+                [0x07, 0x05, 0x02, 0x03, index as u8, 0, 0, 0]
+            );
+        }
+        assert_eq!(
+            frames[0].as_bytes()[8..16],
+            [0xFF, 0xFF, 0x80, 0x03, 0x04, 0x00, 0x03, 0x04]
+        );
+
+        // macros the pad can't hold are refused before anything is sent
+        let sent = falcon.transport.sent_modes().len();
+        let mut too_long = r#macro.clone();
+        too_long.data = vec![r#macro.data[0]; 300];
+        let mut too_slow = r#macro.clone();
+        too_slow.data[1].delay = MAX_DELAY + 1;
+        for r#macro in [too_long, too_slow] {
+            assert!(matches!(
+                falcon.upload_macro(Layer::Two, Key::Three, &r#macro),
+                Err(Error::InvalidConfig { .. })
+            ));
+
+            let mut config = DeviceConfig::default();
+            config.layer_mut(Layer::Five).macros[Key::Eight as usize] = Some(r#macro);
+            assert!(matches!(
+                falcon.write_config(&config),
+                Err(Error::InvalidConfig { .. })
+            ));
+        }
+        assert_eq!(falcon.transport.sent_modes().len(), sent);
+
+        let mut full = r#macro.clone();
+        full.data = vec![r#macro.data[0]; MAX_INPUTS];
+        assert!(full.validate().is_ok());
+        full.data.push(r#macro.data[0]);
+        assert!(full.validate().is_err());
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_read_macro() -> Result<()> {
+        let mut falcon = simulated();
+        let mut r#macro = Macro::new(Repetition::UntilNextKeyPressed);
+        for i in 0..100 {
+            r#macro.add_macro_data(MacroData {
+                key_press: if i % 2 == 0 {
+                    KeyPress::Down
+                } else {
+                    KeyPress::Up
+                },
+                delay: i * 300,
+                key_code: KeyCode::Leftshift,
+            })?;
+        }
+
+        assert!(falcon.read_macro(Layer::Five, Key::Eight)?.data.is_empty());
+
+        falcon.upload_macro(Layer::Five, Key::Eight, &r#macro)?;
+        assert_eq!(falcon.read_macro(Layer::Five, Key::Eight)?, r#macro);
+
+        let mut frames = r#macro.to_frames(Layer::Five, Key::Eight)?;
+        frames[1][8 + 3] = 0xDE;
+        assert_eq!(
+            Macro::from_frames(&frames),
+            Err(DecodeError {
+                field: "macro key code",
+                offset: 264 + 8 + 3,
+                value: 0xDE
+            })
+        );
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_config_round_trip() -> Result<()> {
+        let mut falcon = simulated();
+        let mut config = falcon.read_config()?;
+        assert_eq!(config, DeviceConfig::default());
+
+        let layer = config.layer_mut(Layer::Four);
+        layer.key_codes[Key::Two as usize] = KeyCode::F13;
+        layer.key_colors[Key::Two as usize] = Color::new(0x12, 0x34, 0x56);
+        layer.led_mode = LEDMode::Custom;
+        layer.macros[Key::Six as usize] = Some(Macro {
+            repetition: Repetition::WhilePressed,
+            data: vec![MacroData {
+                key_press: KeyPress::Down,
+                delay: 1,
+                key_code: KeyCode::Z,
+            }],
+        });
+        config.layer_mut(Layer::One).brightness = Brightness::Low;
+
+        falcon.write_config(&config)?;
+        assert_eq!(falcon.transport.sent_modes().last(), Some(&Mode::Finalize));
+
+        falcon.transport.power_cycle();
+        assert_eq!(falcon.read_config()?, config);
+
+        // the cleared macro's frames are still stored, but its key no longer points at them
+        config.layer_mut(Layer::Four).macros[Key::Six as usize] = None;
+        falcon.write_config(&config)?;
+        falcon.transport.power_cycle();
+        assert_eq!(falcon.read_config()?, config);
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test_log::test]
+    fn test_profile() -> Result<()> {
+        use profile::Profile;
+
+        let profile: Profile = r##"
+            [layers.two]
+            led_mode = "Custom"
+            color = "#FFFFFF"
+
+            [layers.two.keys]
+            one = "KPAsterisk"
+
+            [layers.two.colors]
+            five = "#00ff00"
+            six = "hsv(240, 100%, 100%)"
+
+            [layers.two.macros.three]
+            steps = [
+                { press = "down", key = "Leftctrl" },
+                { press = "up", key = "Leftctrl", delay_ms = 20 },
+            ]
+        "##
+        .parse()?;
+
+        let mut falcon = simulated();
+        falcon.apply_profile(&profile)?;
+
+        let config = falcon.read_config()?;
+        let layer = config.layer(Layer::Two);
+        assert_eq!(layer.led_mode, LEDMode::Custom);
+        assert_eq!(layer.key_codes[Key::One as usize], KeyCode::KPAsterisk);
+        assert_eq!(layer.key_colors[Key::Five as usize], Color::new(0, 0xFF, 0));
+        assert_eq!(layer.key_colors[Key::Six as usize], Color::new(0, 0, 0xFF));
+        assert_eq!(
+            layer.macros[Key::Three as usize].as_ref().unwrap().data[1].delay,
+            2
+        );
+        assert_eq!(config.layer(Layer::One), &LayerConfig::default());
+
+        let dumped = falcon.dump_profile()?;
+        assert_eq!(dumped.to_toml()?.parse::<Profile>()?, dumped);
+
+        /// The simulator, stalling macro reads like a pad without them might
+        struct NoMacroRead(Simulator);
+
+        impl Transport for NoMacroRead {
+            fn set_report(&mut self, report: &Report) -> Result<()> {
+                if report[1] == Mode::MacroRead as u8 {
+                    return Err(Error::Usb(rusb::Error::Pipe));
+                }
+                self.0.set_report(report)
+            }
+
+            fn get_report(&mut self, report: &mut Report) -> Result<()> {
+                self.0.get_report(report)
+            }
+        }
+
+        // applying a profile doesn't need macros to read back, but loses the ones that don't
+        let mut falcon = Falcon8::with_transport(NoMacroRead(Simulator::new()));
+        falcon.upload_macro(Layer::Two, Key::Four, &Macro::new(Repetition::WhilePressed))?;
+        falcon.apply_profile(&profile)?;
+        let committed = falcon.transport.0.committed(Layer::Two);
+        assert_eq!(committed.decode()?.led_mode, LEDMode::Custom);
+        assert_eq!(committed.macro_slot(Key::Three), Key::Three.macro_slot());
+        assert_eq!(committed.macro_slot(Key::Four), 0);
+        assert!(falcon.read_config().is_err());
+
+        assert!("[layers.six]".parse::<Profile>().is_err());
+        assert!(r##"layers.one.color = "#12345""##
+            .parse::<Profile>()
+            .is_err());
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_device_selector() {
+        let info = DeviceInfo {
+            bus: 3,
+            address: 7,
+            port_path: vec![2, 1],
+            serial: Some("F8-0042".to_string()),
+        };
+        assert_eq!(
+            info.to_string(),
+            "bus 003 address 007 port 3-2.1 serial F8-0042"
+        );
+
+        assert_eq!(DeviceSelector::new().matches(&info), Ok(()));
+        assert_eq!(
+            DeviceSelector::new()
+                .bus(3)
+                .address(7)
+                .port_path([2, 1])
+                .serial("F8-0042")
+                .matches(&info),
+            Ok(())
+        );
+        assert_eq!(
+            DeviceSelector::new().bus(1).matches(&info),
+            Err(SkipReason::Filtered("bus"))
+        );
+        assert_eq!(
+            DeviceSelector::new().port_path([2]).matches(&info),
+            Err(SkipReason::Filtered("port path"))
+        );
+        assert_eq!(
+            DeviceSelector::new().serial("F8-0043").matches(&info),
+            Err(SkipReason::Filtered("serial"))
+        );
+
+        // a pad that couldn't be opened has no serial to match, which is down to the open error
+        let unreadable = DeviceInfo {
+            serial: None,
+            ..info.clone()
+        };
+        let by_serial = DeviceSelector::new().serial("F8-0042");
+        assert_eq!(
+            by_serial.skip_reason(&unreadable, Err(rusb::Error::Access)),
+            Some(SkipReason::Permissions)
+        );
+        assert_eq!(
+            by_serial.skip_reason(&unreadable, Ok(())),
+            Some(SkipReason::Filtered("serial"))
+        );
+        assert_eq!(
+            by_serial
+                .bus(1)
+                .skip_reason(&unreadable, Err(rusb::Error::Busy)),
+            Some(SkipReason::Filtered("bus"))
+        );
+        assert_eq!(
+            DeviceSelector::new().skip_reason(&unreadable, Err(rusb::Error::Busy)),
+            Some(SkipReason::Busy)
+        );
+        assert_eq!(DeviceSelector::new().skip_reason(&info, Ok(())), None);
+
+        // nothing has this id, and machines without USB access fail to list devices at all
+        match DeviceSelector::new().usb_id(0xFFFF, 0xFFFF).candidates() {
+            Ok(candidates) => assert!(candidates.is_empty()),
+            Err(err) => assert!(matches!(err, Error::Usb(_)), "{err}"),
+        }
+
+        assert_eq!(
+            SkipReason::from(rusb::Error::Access),
+            SkipReason::Permissions
+        );
+        assert_eq!(SkipReason::from(rusb::Error::Busy), SkipReason::Busy);
+
+        let err = Error::NoMatchingDevice {
+            skipped: vec![
+                (info, SkipReason::Busy),
+                (unreadable, SkipReason::Permissions),
+            ],
+        };
+        assert_eq!(
+            err.to_string(),
+            "no Falcon-8 matches\n  bus 003 address 007 port 3-2.1 serial F8-0042: in use by another program\n  bus 003 address 007 port 3-2.1: permission denied"
+        );
+    }
+
+    #[test_log::test]
+    fn test_hotplug_watcher() -> Result<()> {
+        let info = DeviceInfo {
+            bus: 1,
+            address: 4,
+            port_path: vec![2],
+            serial: None,
+        };
+        let timeout = std::time::Duration::ZERO;
+
+        let mut config = DeviceConfig::default();
+        config.layer_mut(Layer::Two).key_codes[Key::One as usize] = KeyCode::F13;
+
+        let (events, source) = std::sync::mpsc::channel();
+        let mut watcher = HotplugWatcher::new(source)
+            .reopen_with(|_| Ok(simulated()))
+            .reapply_config(config.clone());
+
+        assert_eq!(watcher.poll(timeout)?, None);
+
+        events.send(HotplugEvent::Arrived(info.clone())).unwrap();
+        assert_eq!(
+            watcher.poll(timeout)?,
+            Some(HotplugEvent::Arrived(info.clone()))
+        );
+        assert_eq!(watcher.pads().len(), 1);
+        let (_, falcon8) = &mut watcher.pads_mut()[0];
+        assert_eq!(
+            falcon8.transport.committed(Layer::Two)[Key::One.to_index()],
+            KeyCode::F13 as u8
+        );
+        assert_eq!(falcon8.read_config()?, config);
+
+        // coming back from suspend without a leave replaces the stale pad
+        events.send(HotplugEvent::Arrived(info.clone())).unwrap();
+        watcher.poll(timeout)?;
+        assert_eq!(watcher.pads().len(), 1);
+
+        events.send(HotplugEvent::Left(info.clone())).unwrap();
+        assert_eq!(
+            watcher.poll(timeout)?,
+            Some(HotplugEvent::Left(info.clone()))
+        );
+        assert!(watcher.pads().is_empty());
+
+        // without an opener, events are only passed along
+        let (events, source) = std::sync::mpsc::channel();
+        let mut watcher = HotplugWatcher::<_, Simulator>::new(source);
+        events.send(HotplugEvent::Arrived(info.clone())).unwrap();
+        assert_eq!(
+            watcher.poll(timeout)?,
+            Some(HotplugEvent::Arrived(info.clone()))
+        );
+        assert!(watcher.pads().is_empty());
+
+        // a pad that can't be opened is reported and not kept
+        let (events, source) = std::sync::mpsc::channel();
+        let mut watcher = HotplugWatcher::<_, Simulator>::new(source)
+            .reopen_with(|_| Err(Error::Usb(rusb::Error::Access)));
+        events.send(HotplugEvent::Arrived(info)).unwrap();
+        assert!(matches!(
+            watcher.poll(timeout),
+            Err(Error::Usb(rusb::Error::Access))
+        ));
+        assert!(watcher.pads().is_empty());
+
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_input_decoder() {
+        let mut bindings = Report::default();
+        bindings.set_key(Key::One, KeyCode::A);
+        bindings.set_key(Key::Two, KeyCode::Leftctrl);
+        bindings.set_key(Key::Three, KeyCode::Mute);
+        bindings.set_key(Key::Four, KeyCode::MouseRightClick);
+
+        let mut decoder = InputDecoder::new(Layer::One);
+        decoder.set_bindings(Layer::One, &bindings);
+
+        let key = |key, key_code, press| InputEvent::Key {
+            key,
+            key_code,
+            press,
+        };
+
+        assert_eq!(
+            decoder.decode(0, &[0x01, 0, KeyCode::A as u8, 0, 0, 0, 0, 0]),
+            [
+                key(Some(Key::Two), KeyCode::Leftctrl, KeyPress::Down),
+                key(Some(Key::One), KeyCode::A, KeyPress::Down),
+            ]
+        );
+        // nothing changed
+        assert_eq!(
+            decoder.decode(0, &[0x01, 0, KeyCode::A as u8, 0, 0, 0, 0, 0]),
+            []
+        );
+        assert_eq!(
+            decoder.decode(
+                0,
+                &[0x01, 0, KeyCode::A as u8, KeyCode::B as u8, 0, 0, 0, 0]
+            ),
+            [key(None, KeyCode::B, KeyPress::Down)]
+        );
+        assert_eq!(
+            decoder.decode(0, &[0; 8]),
+            [
+                key(Some(Key::Two), KeyCode::Leftctrl, KeyPress::Up),
+                key(Some(Key::One), KeyCode::A, KeyPress::Up),
+                key(None, KeyCode::B, KeyPress::Up),
+            ]
+        );
+
+        assert_eq!(
+            decoder.decode(1, &[0x02, 0xE2, 0x00]),
+            [InputEvent::Consumer {
+                key: Some(Key::Three),
+                usage: 0xE2,
+                press: KeyPress::Down
+            }]
+        );
+        assert_eq!(
+            decoder.decode(1, &[0x02, 0x00, 0x00]),
+            [InputEvent::Consumer {
+                key: Some(Key::Three),
+                usage: 0xE2,
+                press: KeyPress::Up
+            }]
+        );
+
+        assert_eq!(
+            decoder.decode(1, &[0x01, 0b10, 0, 0, 0]),
+            [InputEvent::MouseButton {
+                key: Some(Key::Four),
+                key_code: KeyCode::MouseRightClick,
+                press: KeyPress::Down
+            }]
+        );
+        assert_eq!(decoder.decode(1, &[0x01, 0b10, 5, 5, 0]), []);
+
+        // keys are looked up on the layer the pad switched to
+        assert_eq!(
+            decoder.decode(2, &[0x07, Mode::LayerWrite as u8, 2]),
+            [InputEvent::LayerSwitch(Layer::Two)]
+        );
+        assert_eq!(decoder.layer(), Layer::Two);
+        assert_eq!(
+            decoder.decode(0, &[0, 0, KeyCode::A as u8, 0, 0, 0, 0, 0]),
+            [key(None, KeyCode::A, KeyPress::Down)]
+        );
+
+        // short and unknown reports are ignored
+        assert_eq!(decoder.decode(0, &[0, 0]), []);
+        assert_eq!(decoder.decode(1, &[0x42, 1, 2]), []);
+    }
+
+    #[cfg(feature = "async")]
+    #[test_log::test(tokio::test(start_paused = true))]
+    async fn test_async_falcon8() -> Result<()> {
+        /// The simulator, pausing like a real pad
+        struct Paced(Simulator);
+
+        impl Transport for Paced {
+            fn set_report(&mut self, report: &Report) -> Result<()> {
+                self.0.set_report(report)
+            }
+
+            fn get_report(&mut self, report: &mut Report) -> Result<()> {
+                self.0.get_report(report)
+            }
+
+            fn report_delay(&self) -> std::time::Duration {
+                REPORT_DELAY
+            }
+        }
+
+        let mut falcon = AsyncFalcon8::new(Falcon8::with_transport(Paced(Simulator::new())));
+        let start = tokio::time::Instant::now();
+
+        falcon
+            .lock()
+            .await
+            .key_controls
+            .set_key(Key::One, KeyCode::KPAsterisk);
+        falcon.update_keys().await?;
+        falcon
+            .lock()
+            .await
+            .led_controls
+            .set_mode(LEDMode::Breathing)
+            .set_brightness(Brightness::Max);
+        falcon.update_leds().await?;
+        falcon.update_layer(Layer::Two).await?;
+
+        // every report was followed by a pause on the paused clock
+        assert_eq!(start.elapsed(), REPORT_DELAY * 8);
+
+        let committed = falcon
+            .lock()
+            .await
+            .transport
+            .0
+            .committed(Layer::One)
+            .decode()?;
+        assert_eq!(committed.key_codes[Key::One as usize], KeyCode::KPAsterisk);
+        assert_eq!(falcon.lock().await.transport.0.active_layer(), Layer::Two);
+        assert_eq!(
+            falcon.lock().await.transport.0.sent_modes(),
+            [
+                Mode::KeyRead,
+                Mode::KeyWrite,
+                Mode::Finalize,
+                Mode::KeyRead,
+                Mode::KeyWrite,
+                Mode::LayerWrite
+            ]
+        );
+
+        // the keys are read back from the new layer
+        let report = falcon.get_keys().await?;
+        assert_eq!(report.active_layer()?, Layer::Two);
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test_log::test]
+    fn test_hidraw() -> Result<()> {
+        /// Answers feature ioctls from the simulator
+        struct FakeFd(Simulator);
+
+        impl FeatureDevice for FakeFd {
+            fn set_feature(&mut self, data: &[u8]) -> std::io::Result<usize> {
+                let report = Report::from(data);
+                self.0.set_report(&report).map_err(std::io::Error::other)?;
+                Ok(data.len())
+            }
+
+            fn get_feature(&mut self, data: &mut [u8]) -> std::io::Result<usize> {
+                assert_eq!(data[0], 0x07, "the report id is requested");
+                let mut report = Report::new();
+                self.0
+                    .get_report(&mut report)
+                    .map_err(std::io::Error::other)?;
+                data.copy_from_slice(report.as_ref());
+                Ok(data.len())
+            }
+        }
+
+        let sys = std::env::temp_dir().join(format!("falcon8-sysfs-{}", std::process::id()));
+        let uevent = |node: &str, id: &str, phys: &str, uniq: &str| {
+            let device = sys.join("class/hidraw").join(node).join("device");
+            std::fs::create_dir_all(&device).unwrap();
+            std::fs::write(
+                device.join("uevent"),
+                format!("DRIVER=hid-generic\nHID_ID={id}\nHID_NAME=Falcon-8\nHID_PHYS={phys}\nHID_UNIQ={uniq}\n"),
+            )
+            .unwrap();
+        };
+        uevent(
+            "hidraw0",
+            "0003:0000195D:00006009",
+            "usb-0000:00:14.0-2/input0",
+            "",
+        );
+        uevent(
+            "hidraw1",
+            "0003:0000195D:00006009",
+            "usb-0000:00:14.0-2/input2",
+            "F8-1",
+        );
+        uevent(
+            "hidraw2",
+            "0003:0000046D:0000C52B",
+            "usb-0000:00:14.0-3/input2",
+            "",
+        );
+        uevent("hidraw3", "0005:0000195D:00006009", "bluetooth/input2", "");
+
+        let nodes = HidrawNode::find_in(&sys, std::path::Path::new("/dev"));
+        std::fs::remove_dir_all(&sys).unwrap();
+        let nodes = nodes?;
+        assert_eq!(
+            nodes,
+            [HidrawNode {
+                path: "/dev/hidraw1".into(),
+                phys: "usb-0000:00:14.0-2/input2".to_string(),
+                uniq: "F8-1".to_string(),
+            }]
+        );
+        let unnamed = HidrawNode {
+            path: "/dev/hidraw0".into(),
+            phys: "usb-0000:00:14.0-2/input2".to_string(),
+            uniq: String::new(),
+        };
+        assert_eq!(
+            Error::AmbiguousHidraw {
+                nodes: vec![nodes[0].clone(), unnamed],
+            }
+            .to_string(),
+            "2 Falcon-8s match, narrow the selection\n  \
+             /dev/hidraw1 usb-0000:00:14.0-2/input2 serial F8-1\n  \
+             /dev/hidraw0 usb-0000:00:14.0-2/input2"
+        );
+        assert_eq!(
+            HidrawNode::find_in(
+                std::path::Path::new("/nonexistent"),
+                std::path::Path::new("/dev"),
+            )?,
+            []
+        );
+
+        let mut falcon = Falcon8::with_transport(HidrawTransport::new(FakeFd(Simulator::new())));
+        falcon.key_controls.set_key(Key::Three, KeyCode::F13);
+        falcon.update_keys()?;
+
+        let simulator = &falcon.transport.device.0;
+        assert_eq!(
+            simulator.committed(Layer::One).decode()?.key_codes[Key::Three as usize],
+            KeyCode::F13
+        );
+        assert_eq!(falcon.transport.report_delay(), REPORT_DELAY);
+
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_color() -> Result<()> {
+        let orange = Color::new(0xFF, 0xA5, 0x00);
+
+        assert_eq!("#FFA500".parse::<Color>()?, orange);
+        assert_eq!("#ffa500".parse::<Color>()?, orange);
+        assert_eq!("#F00".parse::<Color>()?, Color::new(0xFF, 0, 0));
+        assert_eq!("rgb(255, 165, 0)".parse::<Color>()?, orange);
+        assert_eq!(" RGB( 255,165,0 ) ".parse::<Color>()?, orange);
+        assert_eq!("orange".parse::<Color>()?, orange);
+        assert_eq!(
+            "RebeccaPurple".parse::<Color>()?,
+            Color::new(0x66, 0x33, 0x99)
+        );
+        assert_eq!(
+            "hsv(0, 100%, 100%)".parse::<Color>()?,
+            Color::new(0xFF, 0, 0)
+        );
+        assert_eq!(
+            "hsv(120deg, 100, 50)".parse::<Color>()?,
+            Color::new(0, 0x80, 0)
+        );
+        assert_eq!(
+            "hsl(240, 100%, 50%)".parse::<Color>()?,
+            Color::new(0, 0, 0xFF)
+        );
+        assert_eq!("hsl(0, 0%, 100%)".parse::<Color>()?, Color::WHITE);
+
+        for invalid in [
+            "#12345",
+            "#GG0000",
+            "rgb(256, 0, 0)",
+            "rgb(1, 2)",
+            "hsv(0, 101%, 0%)",
+            "hsv(red, 0%, 0%)",
+            "blurple",
+            "",
+        ] {
+            assert!(invalid.parse::<Color>().is_err(), "{invalid:?} parsed");
+        }
+
+        assert_eq!(orange.to_string(), "#FFA500");
+        assert_eq!(orange.to_string().parse::<Color>()?, orange);
+
+        let (hue, saturation, value) = orange.to_hsv();
+        assert!((hue - 38.8).abs() < 0.1);
+        assert_eq!((saturation, value), (1.0, 1.0));
+        assert_eq!(Color::from_hsv(hue, saturation, value), orange);
+        let (hue, saturation, lightness) = orange.to_hsl();
+        assert_eq!(Color::from_hsl(hue, saturation, lightness), orange);
+        assert_eq!(
+            Color::new(0x80, 0x80, 0x80).to_hsl(),
+            (0.0, 0.0, 128.0 / 255.0)
+        );
+
+        assert_eq!(orange.scale(0.5), Color::new(0x80, 0x53, 0));
+        assert_eq!(orange.scale(2.0), Color::new(0xFF, 0xFF, 0));
+        assert_eq!(
+            Color::new(0x80, 0xFF, 0).gamma(2.2),
+            Color::new(0x38, 0xFF, 0)
+        );
+        assert_eq!(Color::from((1, 2, 3)), Color::new(1, 2, 3));
+        assert_eq!(<(u8, u8, u8)>::from(orange), (0xFF, 0xA5, 0));
+
+        // every CSS name is reachable through the binary search
+        assert_eq!("aliceblue".parse::<Color>()?, Color::new(0xF0, 0xF8, 0xFF));
+        assert_eq!(
+            "yellowgreen".parse::<Color>()?,
+            Color::new(0x9A, 0xCD, 0x32)
+        );
+        assert_eq!("grey".parse::<Color>()?, "gray".parse::<Color>()?);
+
+        let mut report = Report::default();
+        report.set_key_color(Key::Three, "teal".parse::<Color>()?);
+        report.set_key_color(Key::Four, (1, 2, 3));
+        assert_eq!(
+            report.decode()?.key_colors[Key::Three as usize],
+            Color::new(0, 0x80, 0x80)
+        );
+        assert!(format!("{report:?}").contains("key_three_rgb: #008080"));
+
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_key_code_conversions() -> Result<()> {
+        for (name, key_code) in [
+            ("KPAsterisk", KeyCode::KPAsterisk),
+            ("kpasterisk", KeyCode::KPAsterisk),
+            ("kp_*", KeyCode::KPAsterisk),
+            ("KEY_KPASTERISK", KeyCode::KPAsterisk),
+            ("ctrl", KeyCode::Leftctrl),
+            ("KEY_LEFTCTRL", KeyCode::Leftctrl),
+            ("Super", KeyCode::Leftmeta),
+            ("vol+", KeyCode::VolumeUp),
+            ("Vol-", KeyCode::VolumeDown),
+            ("KEY_VOLUMEUP", KeyCode::VolumeUp),
+            ("mute", KeyCode::Mute),
+            ("_MUTE", KeyCode::_MUTE),
+            ("KEY_102ND", KeyCode::_102ND),
+            ("1", KeyCode::One),
+            ("KEY_1", KeyCode::One),
+            ("page up", KeyCode::Pageup),
+            ("esc", KeyCode::Esc),
+            ("Escape", KeyCode::Esc),
+            (" a ", KeyCode::A),
+            ("lmb", KeyCode::MouseLeftClick),
+            ("layer3", KeyCode::Layer3),
+        ] {
+            assert_eq!(name.parse::<KeyCode>()?, key_code, "{name:?}");
+        }
+        assert!("".parse::<KeyCode>().is_err());
+        assert!("_".parse::<KeyCode>().is_err());
+        assert!("hyper".parse::<KeyCode>().is_err());
+
+        // every variant name parses back to its variant
+        for key_code in KeyCode::all() {
+            assert_eq!(format!("{key_code:?}").parse::<KeyCode>()?, key_code);
+        }
+
+        assert_eq!(KeyCode::try_from(0xFA)?, KeyCode::LockUnlockWholeS);
+        assert!(KeyCode::try_from(0xBA).is_err());
+        assert!(KeyCode::try_from(0xFF).is_err());
+
+        assert_eq!(KeyCode::A.to_evdev(), Some(30));
+        assert_eq!(KeyCode::Enter.to_evdev(), Some(28));
+        assert_eq!(KeyCode::Leftmeta.to_evdev(), Some(125));
+        assert_eq!(KeyCode::MouseForward.to_evdev(), Some(0x114));
+        assert_eq!(KeyCode::Layer1.to_evdev(), None);
+        assert_eq!(KeyCode::ErrOvf.to_evdev(), None);
+        assert_eq!(KeyCode::from_evdev(30), Some(KeyCode::A));
+        assert_eq!(KeyCode::from_evdev(113), Some(KeyCode::Mute));
+        assert_eq!(KeyCode::from_evdev(43), Some(KeyCode::Backslash));
+        assert_eq!(KeyCode::from_evdev(0), None);
+        assert_eq!(KeyCode::from_evdev(0x2FF), None);
+        for key_code in KeyCode::all() {
+            if let Some(code) = key_code.to_evdev() {
+                let back = KeyCode::from_evdev(code).unwrap();
+                assert_eq!(back.to_evdev(), Some(code), "{key_code:?}");
+            }
+        }
+
+        assert_eq!(KeyCode::A.hid_usage(), Some((0x07, 0x04)));
+        assert_eq!(KeyCode::Leftshift.hid_usage(), Some((0x07, 0xE1)));
+        assert_eq!(KeyCode::PlayPause.hid_usage(), Some((0x0C, 0xCD)));
+        assert_eq!(KeyCode::MouseRightClick.hid_usage(), Some((0x09, 2)));
+        assert_eq!(KeyCode::LayerCycle.hid_usage(), None);
+        assert_eq!(KeyCode::Disable.hid_usage(), None);
+        for key_code in KeyCode::all() {
+            if let Some((page, id)) = key_code.hid_usage() {
+                let back = KeyCode::from_hid_usage(page, id).unwrap();
+                assert_eq!(back.hid_usage(), Some((page, id)), "{key_code:?}");
+            }
+        }
+        assert_eq!(KeyCode::from_hid_usage(0x0C, 0x1234), None);
+
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_key_code_table() {
+        let table = [
+            (0x00, KeyCode::Disable),
+            (0x01, KeyCode::ErrOvf),
+            (0x02, KeyCode::PostFail),
+            (0x03, KeyCode::ErrUndefined),
+            (0x04, KeyCode::A),
+            (0x05, KeyCode::B),
+            (0x06, KeyCode::C),
+            (0x07, KeyCode::D),
+            (0x08, KeyCode::E),
+            (0x09, KeyCode::F),
+            (0x0A, KeyCode::G),
+            (0x0B, KeyCode::H),
+            (0x0C, KeyCode::I),
+            (0x0D, KeyCode::J),
+            (0x0E, KeyCode::K),
+            (0x0F, KeyCode::L),
+            (0x10, KeyCode::M),
+            (0x11, KeyCode::N),
+            (0x12, KeyCode::O),
+            (0x13, KeyCode::P),
+            (0x14, KeyCode::Q),
+            (0x15, KeyCode::R),
+            (0x16, KeyCode::S),
+            (0x17, KeyCode::T),
+            (0x18, KeyCode::U),
+            (0x19, KeyCode::V),
+            (0x1A, KeyCode::W),
+            (0x1B, KeyCode::X),
+            (0x1C, KeyCode::Y),
+            (0x1D, KeyCode::Z),
+            (0x1E, KeyCode::One),
+            (0x1F, KeyCode::Two),
+            (0x20, KeyCode::Three),
+            (0x21, KeyCode::Four),
+            (0x22, KeyCode::Five),
+            (0x23, KeyCode::Six),
+            (0x24, KeyCode::Seven),
+            (0x25, KeyCode::Eight),
+            (0x26, KeyCode::Nine),
+            (0x27, KeyCode::Zero),
+            (0x28, KeyCode::Enter),
+            (0x29, KeyCode::Esc),
+            (0x2A, KeyCode::Backspace),
+            (0x2B, KeyCode::Tab),
+            (0x2C, KeyCode::Space),
+            (0x2D, KeyCode::Minus),
+            (0x2E, KeyCode::Equal),
+            (0x2F, KeyCode::Leftbrace),
+            (0x30, KeyCode::Rightbrace),
+            (0x31, KeyCode::Backslash),
+            (0x32, KeyCode::Hashtilde),
+            (0x33, KeyCode::Semicolon),
+            (0x34, KeyCode::Apostrophe),
+            (0x35, KeyCode::Grave),
+            (0x36, KeyCode::Comma),
+            (0x37, KeyCode::Dot),
+            (0x38, KeyCode::Slash),
+            (0x39, KeyCode::Capslock),
+            (0x3A, KeyCode::F1),
+            (0x3B, KeyCode::F2),
+            (0x3C, KeyCode::F3),
+            (0x3D, KeyCode::F4),
+            (0x3E, KeyCode::F5),
+            (0x3F, KeyCode::F6),
+            (0x40, KeyCode::F7),
+            (0x41, KeyCode::F8),
+            (0x42, KeyCode::F9),
+            (0x43, KeyCode::F10),
+            (0x44, KeyCode::F11),
+            (0x45, KeyCode::F12),
+            (0x46, KeyCode::Sysrq),
+            (0x47, KeyCode::Scrolllock),
+            (0x48, KeyCode::Pause),
+            (0x49, KeyCode::Insert),
+            (0x4A, KeyCode::Home),
+            (0x4B, KeyCode::Pageup),
+            (0x4C, KeyCode::Delete),
+            (0x4D, KeyCode::End),
+            (0x4E, KeyCode::Pagedown),
+            (0x4F, KeyCode::Right),
+            (0x50, KeyCode::Left),
+            (0x51, KeyCode::Down),
+            (0x52, KeyCode::Up),
+            (0x53, KeyCode::Numlock),
+            (0x54, KeyCode::KPSlash),
+            (0x55, KeyCode::KPAsterisk),
+            (0x56, KeyCode::KPMinus),
+            (0x57, KeyCode::KPPlus),
+            (0x58, KeyCode::KPEnter),
+            (0x59, KeyCode::KP1),
+            (0x5A, KeyCode::KP2),
+            (0x5B, KeyCode::KP3),
+            (0x5C, KeyCode::KP4),
+            (0x5D, KeyCode::KP5),
+            (0x5E, KeyCode::KP6),
+            (0x5F, KeyCode::KP7),
+            (0x60, KeyCode::KP8),
+            (0x61, KeyCode::KP9),
+            (0x62, KeyCode::KP0),
+            (0x63, KeyCode::KPDot),
+            (0x64, KeyCode::_102ND),
+            (0x65, KeyCode::Compose),
+            (0x66, KeyCode::Power),
+            (0x67, KeyCode::KPEqual),
+            (0x68, KeyCode::F13),
+            (0x69, KeyCode::F14),
+            (0x6A, KeyCode::F15),
+            (0x6B, KeyCode::F16),
+            (0x6C, KeyCode::F17),
+            (0x6D, KeyCode::F18),
+            (0x6E, KeyCode::F19),
+            (0x6F, KeyCode::F20),
+            (0x70, KeyCode::F21),
+            (0x71, KeyCode::F22),
+            (0x72, KeyCode::F23),
+            (0x73, KeyCode::F24),
+            (0x74, KeyCode::Open),
+            (0x75, KeyCode::Help),
+            (0x76, KeyCode::Props),
+            (0x77, KeyCode::Front),
+            (0x78, KeyCode::Stop),
+            (0x79, KeyCode::Again),
+            (0x7A, KeyCode::Undo),
+            (0x7B, KeyCode::Cut),
+            (0x7C, KeyCode::Copy),
+            (0x7D, KeyCode::Paste),
+            (0x7E, KeyCode::Find),
+            (0x7F, KeyCode::_MUTE),
+            (0x80, KeyCode::_VOLUP),
+            (0x81, KeyCode::_VOLDN),
+            (0x82, KeyCode::_CAPSLOCK),
+            (0x83, KeyCode::_NUMLOCK),
+            (0x84, KeyCode::_SCROLLLOCK),
+            (0x85, KeyCode::_KPCOMMA),
+            (0x86, KeyCode::_EQUALS),
+            (0x87, KeyCode::_RO),
+            (0x88, KeyCode::_KATAKANAHIRAGANA),
+            (0x89, KeyCode::_YEN),
+            (0x8A, KeyCode::_HENKAN),
+            (0x8B, KeyCode::_MUHENKAN),
+            (0x8C, KeyCode::_KPJPCOMMA),
+            (0x8D, KeyCode::_I7),
+            (0x8E, KeyCode::_I8),
+            (0x8F, KeyCode::_I9),
+            (0x90, KeyCode::_HANGEUL),
+            (0x91, KeyCode::_HANJA),
+            (0x92, KeyCode::_KATAKANA),
+            (0x93, KeyCode::_HIRAGANA),
+            (0x94, KeyCode::_ZENKAKUHANKAKU),
+            (0x95, KeyCode::_L6),
+            (0x96, KeyCode::_L7),
+            (0x97, KeyCode::_L8),
+            (0x98, KeyCode::_L9),
+            (0x99, KeyCode::_AE),
+            (0x9A, KeyCode::_SYSREQ),
+            (0x9B, KeyCode::_CANCEL),
+            (0x9C, KeyCode::_CLEAR),
+            (0x9D, KeyCode::_PRIOR),
+            (0x9E, KeyCode::_RETURN),
+            (0x9F, KeyCode::_SEPARATOR),
+            (0xA0, KeyCode::_OUT),
+            (0xA1, KeyCode::_OPER),
+            (0xA2, KeyCode::_CLEARAGAIN),
+            (0xA3, KeyCode::_CRSEL),
+            (0xA4, KeyCode::MediaLaunch),
+            (0xA5, KeyCode::MediaStop),
+            (0xA6, KeyCode::MediaPrevious),
+            (0xA7, KeyCode::PlayPause),
+            (0xA8, KeyCode::MediaNext),
+            (0xA9, KeyCode::Mute),
+            (0xAA, KeyCode::VolumeDown),
+            (0xAB, KeyCode::VolumeUp),
+            // assumed to be forwarded as keyboard usages, see KeyCode
+            (0xB0, KeyCode::KP00),
+            (0xB1, KeyCode::KP000),
+            (0xB2, KeyCode::ThousandsSeparator),
+            (0xB3, KeyCode::DecimalSeparator),
+            (0xB4, KeyCode::Layer1),
+            (0xB5, KeyCode::Layer2),
+            (0xB6, KeyCode::Layer3),
+            (0xB7, KeyCode::Layer4),
+            (0xB8, KeyCode::Layer5),
+            (0xB9, KeyCode::LayerCycle),
+            (0xBB, KeyCode::LEDBrightnessCycle),
+            (0xBC, KeyCode::LedModeCycle),
+            (0xBE, KeyCode::MouseLeftClick),
+            (0xBF, KeyCode::MouseRightClick),
+            (0xC0, KeyCode::MouseMiddleClick),
+            (0xC3, KeyCode::MouseBackward),
+            (0xC4, KeyCode::MouseForward),
+            // assumed, like 0xB0 to 0xB3
+            (0xC5, KeyCode::KPLess),
+            (0xC6, KeyCode::KPGreater),
+            (0xC7, KeyCode::KPAmpersand),
+            (0xC8, KeyCode::KPDoubleAmpersand),
+            (0xC9, KeyCode::KPPipe),
+            (0xCA, KeyCode::KPDoublePipe),
+            (0xCB, KeyCode::KPColon),
+            (0xCC, KeyCode::KPHash),
+            (0xCD, KeyCode::KPSpace),
+            (0xCE, KeyCode::KPAt),
+            (0xCF, KeyCode::KPExclamation),
+            (0xD0, KeyCode::KPMemoryStore),
+            (0xD1, KeyCode::KPMemoryRecall),
+            (0xD2, KeyCode::KPMemoryClear),
+            (0xD3, KeyCode::KPMemoryAdd),
+            (0xD4, KeyCode::KPMemorySubtract),
+            (0xD5, KeyCode::KPMemoryMultiply),
+            (0xD6, KeyCode::KPMemoryDivide),
+            (0xD7, KeyCode::KPPlusMinus),
+            (0xD8, KeyCode::KPClear),
+            (0xD9, KeyCode::KPClearEntry),
+            (0xDA, KeyCode::KPBinary),
+            (0xDB, KeyCode::KPOctal),
+            (0xDC, KeyCode::KPDecimal),
+            (0xDD, KeyCode::KPHexadecimal),
+            // assumed, like 0xB0 to 0xB3
+            (0xE0, KeyCode::_LEFTCTRL),
+            (0xE1, KeyCode::_LEFTSHIFT),
+            (0xE2, KeyCode::_LEFTALT),
+            (0xE3, KeyCode::_LEFTMETA),
+            (0xE4, KeyCode::Rightctrl),
+            (0xE5, KeyCode::Rightshift),
+            (0xE6, KeyCode::Rightalt),
+            (0xE7, KeyCode::Rightmeta),
+            (0xE8, KeyCode::_MEDIAPLAYPAUSE),
+            (0xE9, KeyCode::_MEDIASTOPCD),
+            (0xEA, KeyCode::_MEDIAPREVIOUSSONG),
+            (0xEB, KeyCode::_MEDIANEXTSONG),
+            (0xEC, KeyCode::_MEDIAEJECTCD),
+            (0xED, KeyCode::_MEDIAVOLUMEUP),
+            (0xEE, KeyCode::_MEDIAVOLUMEDOWN),
+            (0xEF, KeyCode::_MEDIAMUTE),
+            (0xF0, KeyCode::_MEDIAWWW),
+            (0xF1, KeyCode::_MEDIABACK),
+            (0xF2, KeyCode::_MEDIAFORWARD),
+            (0xF3, KeyCode::_MEDIASTOP),
+            (0xF4, KeyCode::_MEDIAFIND),
+            (0xF5, KeyCode::_MEDIASCROLLUP),
+            (0xF6, KeyCode::_MEDIASCROLLDOWN),
+            (0xF7, KeyCode::DoNotUseLaunchProgram),
+            (0xF8, KeyCode::SetMacro),
+            (0xFA, KeyCode::LockUnlockWholeS),
+            (0xFB, KeyCode::Leftctrl),
+            (0xFC, KeyCode::Leftshift),
+            (0xFD, KeyCode::Leftalt),
+            (0xFE, KeyCode::Leftmeta),
+        ];
+
+        assert_eq!(table.len(), KeyCode::all().count());
+        for (byte, key_code) in table {
+            assert_eq!(key_code as u8, byte, "{key_code:?}");
+        }
+        for byte in 0..=u8::MAX {
+            let key_code = table.iter().find(|(b, _)| *b == byte).map(|(_, k)| *k);
+            assert_eq!(KeyCode::try_from(byte).ok(), key_code, "0x{byte:02X}");
+        }
+
+        for (key_code, kind) in [
+            (KeyCode::A, KeyKind::Keyboard),
+            // the bytes only assumed to be forwarded
+            (KeyCode::Rightctrl, KeyKind::Unsupported),
+            (KeyCode::KPClear, KeyKind::Unsupported),
+            (KeyCode::_MEDIAWWW, KeyKind::Unsupported),
+            (KeyCode::Leftctrl, KeyKind::Keyboard),
+            (KeyCode::Mute, KeyKind::Consumer),
+            (KeyCode::MediaLaunch, KeyKind::Consumer),
+            (KeyCode::MouseForward, KeyKind::Mouse),
+            (KeyCode::Disable, KeyKind::PadFunction),
+            (KeyCode::Layer1, KeyKind::PadFunction),
+            (KeyCode::LockUnlockWholeS, KeyKind::PadFunction),
+            (KeyCode::DoNotUseLaunchProgram, KeyKind::Unsupported),
+            (KeyCode::SetMacro, KeyKind::Unsupported),
+            (KeyCode::KP00, KeyKind::Unsupported),
+            (KeyCode::_I7, KeyKind::Unsupported),
+        ] {
+            assert_eq!(key_code.kind(), kind, "{key_code:?}");
+        }
+
+        assert_eq!(KeyCode::Rightalt.to_evdev(), Some(100));
+        assert_eq!(KeyCode::_MEDIASTOPCD.to_evdev(), Some(166));
+        assert_eq!(KeyCode::from_evdev(166), Some(KeyCode::MediaStop));
+        assert_eq!(KeyCode::from_evdev(97), Some(KeyCode::Rightctrl));
+        assert_eq!(KeyCode::from_hid_usage(0x07, 0xE0), Some(KeyCode::Leftctrl));
+        assert_eq!(KeyCode::from_hid_usage(0x07, 0xB4), None);
+        assert_eq!("altgr".parse::<KeyCode>().ok(), Some(KeyCode::Rightalt));
+
+        // the right hand modifiers come in the high bits of a boot keyboard report
+        let mut decoder = InputDecoder::new(Layer::One);
+        assert_eq!(
+            decoder.decode(0, &[0b0001_0001, 0, 0, 0, 0, 0, 0, 0]),
+            [
+                InputEvent::Key {
+                    key: None,
+                    key_code: KeyCode::Leftctrl,
+                    press: KeyPress::Down
+                },
+                InputEvent::Key {
+                    key: None,
+                    key_code: KeyCode::Rightctrl,
+                    press: KeyPress::Down
+                },
+            ]
+        );
+    }
+
+    #[test_log::test]
+    fn test_bindings() -> Result<()> {
+        let copy_tab = Binding::new(Modifiers::CTRL | Modifiers::SHIFT, KeyCode::T);
+
+        assert_eq!("ctrl+shift+t".parse::<Binding>()?, copy_tab);
+        assert_eq!(" Shift + CTRL + T ".parse::<Binding>()?, copy_tab);
+        assert_eq!(copy_tab.to_string(), "Leftctrl+Leftshift+T");
+        assert_eq!(copy_tab.to_string().parse::<Binding>()?, copy_tab);
+        assert_eq!(
+            "alt+vol+".parse::<Binding>()?,
+            Binding::new(Modifiers::ALT, KeyCode::VolumeUp)
+        );
+        assert_eq!("kp+".parse::<Binding>()?, KeyCode::KPPlus.into());
+        assert_eq!(
+            "altgr+e".parse::<Binding>()?,
+            Binding::new(Modifiers::RIGHT_ALT, KeyCode::E)
+        );
+        assert!("ctrl+".parse::<Binding>().is_err());
+        assert!("hyper+a".parse::<Binding>().is_err());
+
+        // only what fits in a byte is bound directly
+        assert_eq!(Binding::from(KeyCode::A).key_code(), Some(KeyCode::A));
+        assert_eq!(
+            "ctrl".parse::<Binding>()?.key_code(),
+            Some(KeyCode::Leftctrl)
+        );
+        assert_eq!(
+            Binding::new(Modifiers::META, KeyCode::Disable).key_code(),
+            Some(KeyCode::Leftmeta)
+        );
+        assert_eq!(copy_tab.key_code(), None);
+        assert_eq!("ctrl+shift".parse::<Binding>()?.key_code(), None);
+
+        let step = |key_press, delay, key_code| MacroData {
+            key_press,
+            delay,
+            key_code,
+        };
+        assert_eq!(
+            copy_tab.to_macro(),
+            Macro {
+                repetition: Repetition::UntilNextKeyPressed,
+                data: vec![
+                    step(KeyPress::Down, 0, KeyCode::Leftctrl),
+                    step(KeyPress::Down, 0, KeyCode::Leftshift),
+                    step(KeyPress::Down, 0, KeyCode::T),
+                    step(KeyPress::Up, 1, KeyCode::T),
+                    step(KeyPress::Up, 0, KeyCode::Leftshift),
+                    step(KeyPress::Up, 0, KeyCode::Leftctrl),
+                ],
+            }
+        );
+
+        let mut falcon = simulated();
+        falcon.key_controls.set_key(Key::One, KeyCode::KPAsterisk);
+        falcon.key_controls.set_binding(Key::Two, copy_tab);
+        falcon
+            .key_controls
+            .set_binding(Key::Three, Modifiers::SHIFT);
+        assert_eq!(falcon.key_controls.binding(Key::Two), copy_tab);
+        falcon.update_keys()?;
+
+        assert_eq!(
+            falcon.transport.sent_modes(),
+            [
+                Mode::KeyRead,
+                Mode::KeyWrite,
+                Mode::MacroWrite,
+                Mode::MacroWrite,
+                Mode::MacroWrite,
+                Mode::Finalize
+            ]
+        );
+        let committed = falcon.transport.committed(Layer::One).decode()?;
+        assert_eq!(committed.key_codes[Key::One as usize], KeyCode::KPAsterisk);
+        assert_eq!(committed.key_codes[Key::Three as usize], KeyCode::Leftshift);
+        assert_eq!(
+            falcon.read_macro(Layer::One, Key::Two)?,
+            copy_tab.to_macro()
+        );
+
+        // a plain key code clears the modifiers again, and the macro slot on the pad
+        falcon.key_controls.set_key(Key::Two, KeyCode::T);
+        assert_eq!(
+            falcon.key_controls.keys[Key::Two as usize].modifiers,
+            Modifiers::NONE
+        );
+        falcon.update_keys()?;
+        let committed = falcon.transport.committed(Layer::One);
+        assert_eq!(committed[0x0A + Key::Two.to_macro_index()], 0);
+        assert_eq!(committed.decode()?.key_codes[Key::Two as usize], KeyCode::T);
+
+        // macros uploaded on their own survive updating the keys, once the slot was cleared too
+        falcon.upload_macro(Layer::One, Key::One, &copy_tab.to_macro())?;
+        falcon.upload_macro(Layer::One, Key::Two, &copy_tab.to_macro())?;
+        falcon.update_keys()?;
+        let committed = falcon.transport.committed(Layer::One);
+        assert_eq!(committed.macro_slot(Key::One), Key::One.macro_slot());
+        assert_eq!(committed.macro_slot(Key::Two), Key::Two.macro_slot());
+
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_macro_script() -> Result<()> {
+        let step = |key_press, delay, key_code| MacroData {
+            key_press,
+            delay,
+            key_code,
+        };
+
+        let copy: Macro = "down ctrl; tap c; wait 30ms; up ctrl; repeat while-pressed".parse()?;
+        assert_eq!(
+            copy,
+            Macro {
+                repetition: Repetition::WhilePressed,
+                data: vec![
+                    step(KeyPress::Down, 0, KeyCode::Leftctrl),
+                    step(KeyPress::Down, 0, KeyCode::C),
+                    step(KeyPress::Up, 0, KeyCode::C),
+                    step(KeyPress::Up, 3, KeyCode::Leftctrl),
+                ],
+            }
+        );
+        assert_eq!(
+            copy.to_string(),
+            "down Leftctrl\ntap C\nwait 30ms\nup Leftctrl\nrepeat while-pressed"
+        );
+        assert_eq!(copy.to_string().parse::<Macro>()?, copy);
+
+        // comments, blank statements, waits adding up and shortcuts
+        let r#macro: Macro =
+            "# paste twice\n\n  tap ctrl+v;; wait 0.5s\nwait 1s\ntap ctrl+v".parse()?;
+        assert_eq!(r#macro.repetition, Repetition::UntilNextKeyPressed);
+        assert_eq!(r#macro.data.len(), 8);
+        assert_eq!(
+            r#macro.data[4],
+            step(KeyPress::Down, 150, KeyCode::Leftctrl)
+        );
+        assert_eq!(r#macro.to_string().parse::<Macro>()?, r#macro);
+
+        // decimal seconds are exact
+        let r#macro: Macro = "wait 2.01s; tap a; wait 4.07s; tap b; wait 8.030s; tap c".parse()?;
+        let delays = r#macro
+            .data
+            .iter()
+            .map(|data| data.delay)
+            .collect::<Vec<_>>();
+        assert_eq!(delays, [201, 0, 407, 0, 803, 0]);
+
+        let syntax_error = |text: &str| match text.parse::<Macro>() {
+            Err(Error::Syntax { line, column, .. }) => (line, column),
+            other => panic!("expected a syntax error, got {other:?}"),
+        };
+        assert_eq!(syntax_error("down ctrl\n  tap nope"), (2, 7));
+        assert_eq!(syntax_error("tap a; frob b"), (1, 8));
+        assert_eq!(syntax_error("tap a;wait 25ms; tap b"), (1, 12));
+        assert_eq!(syntax_error("up"), (1, 1));
+        assert_eq!(syntax_error("up a b"), (1, 6));
+        assert_eq!(syntax_error("tap a\nwait 10ms"), (2, 1));
+        assert_eq!(syntax_error("wait 400s; tap a"), (1, 6));
+        assert_eq!(syntax_error("wait 0.015s; tap a"), (1, 6));
+        assert_eq!(syntax_error("wait 1e3ms; tap a"), (1, 6));
+        assert_eq!(
+            syntax_error("repeat while-pressed\nrepeat while-pressed"),
+            (2, 1)
+        );
+        assert_eq!(syntax_error("repeat forever"), (1, 8));
+
+        // 120 taps fill the macro, one more doesn't fit
+        let taps = "tap a\n".repeat(120);
+        assert_eq!(taps.parse::<Macro>()?.data.len(), MAX_INPUTS);
+        assert_eq!(syntax_error(&format!("{taps}tap b")), (121, 1));
+
+        #[cfg(feature = "serde")]
+        {
+            let profile: profile::MacroProfile =
+                toml::from_str("repetition = \"WhilePressed\"\nscript = \"tap a\"").unwrap();
+            assert_eq!(
+                profile.to_macro()?,
+                Macro {
+                    repetition: Repetition::WhilePressed,
+                    data: vec![
+                        step(KeyPress::Down, 0, KeyCode::A),
+                        step(KeyPress::Up, 0, KeyCode::A),
+                    ],
+                }
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_macro_from_text() -> Result<()> {
+        let keys = |layout: Layout, c| layout.keystrokes(c).unwrap();
+        let shift = |key_code| Binding::new(Modifiers::SHIFT, key_code);
+
+        assert_eq!(keys(Layout::Us, '|'), [shift(KeyCode::Backslash)]);
+        assert_eq!(keys(Layout::Uk, '@'), [shift(KeyCode::Apostrophe)]);
+        assert_eq!(keys(Layout::Uk, '#'), [KeyCode::Hashtilde.into()]);
+        assert_eq!(keys(Layout::Uk, '\\'), [KeyCode::_102ND.into()]);
+        // AltGr characters wait on the Rightalt byte being verified
+        assert_eq!(Layout::Uk.keystrokes('€'), None);
+        assert_eq!(keys(Layout::De, 'z'), [KeyCode::Y.into()]);
+        assert_eq!(Layout::De.keystrokes('@'), None);
+        assert_eq!(keys(Layout::De, 'Ö'), [shift(KeyCode::Semicolon)]);
+        // dead keys type themselves followed by a space, and accents on what follows
+        assert_eq!(
+            keys(Layout::De, '^'),
+            [KeyCode::Grave.into(), KeyCode::Space.into()]
+        );
+        assert_eq!(
+            keys(Layout::De, 'é'),
+            [KeyCode::Equal.into(), KeyCode::E.into()]
+        );
+        assert_eq!(keys(Layout::Fr, 'a'), [KeyCode::Q.into()]);
+        assert_eq!(keys(Layout::Fr, '1'), [shift(KeyCode::One)]);
+        assert_eq!(keys(Layout::Fr, 'é'), [KeyCode::Two.into()]);
+        assert_eq!(
+            keys(Layout::Fr, 'ë'),
+            [shift(KeyCode::Leftbrace), KeyCode::E.into()]
+        );
+        assert_eq!(Layout::Us.keystrokes('é'), None);
+        assert_eq!("GB".parse::<Layout>()?, Layout::Uk);
+        assert!("dvorak".parse::<Layout>().is_err());
+
+        // every layout types plain ASCII letters and digits
+        for layout in Layout::ALL {
+            Macro::from_text("The quick brown fox: 0123456789", layout)?;
+        }
+
+        // shift stays held across capitals
+        let step = |key_press, key_code| MacroData {
+            key_press,
+            delay: 0,
+            key_code,
+        };
+        assert_eq!(
+            Macro::from_text("AB c\r\n", Layout::Us)?,
+            Macro {
+                repetition: Repetition::UntilNextKeyPressed,
+                data: vec![
+                    step(KeyPress::Down, KeyCode::Leftshift),
+                    step(KeyPress::Down, KeyCode::A),
+                    step(KeyPress::Up, KeyCode::A),
+                    step(KeyPress::Down, KeyCode::B),
+                    step(KeyPress::Up, KeyCode::B),
+                    step(KeyPress::Up, KeyCode::Leftshift),
+                    step(KeyPress::Down, KeyCode::Space),
+                    step(KeyPress::Up, KeyCode::Space),
+                    step(KeyPress::Down, KeyCode::C),
+                    step(KeyPress::Up, KeyCode::C),
+                    step(KeyPress::Down, KeyCode::Enter),
+                    step(KeyPress::Up, KeyCode::Enter),
+                ],
+            }
+        );
+
+        match Macro::from_text("naïve → ïn", Layout::Us) {
+            Err(Error::InvalidConfig { reason, .. }) => {
+                assert_eq!(
+                    reason,
+                    "the us layout can't type 'ï' (U+00EF), '→' (U+2192)"
+                )
+            }
+            other => panic!("expected untypeable characters, got {other:?}"),
+        }
+        assert!(Macro::from_text("naïve", Layout::Fr).is_ok());
+        match Macro::from_text("a@b.de", Layout::De) {
+            Err(Error::InvalidConfig { reason, .. }) => {
+                assert_eq!(reason, "the de layout can't type '@' (U+0040)")
+            }
+            other => panic!("expected AltGr characters to be refused, got {other:?}"),
+        }
+
+        assert_eq!(
+            Macro::from_text(&"a".repeat(120), Layout::Us)?.data.len(),
+            MAX_INPUTS
+        );
+        match Macro::from_text(&"a".repeat(121), Layout::Us) {
+            Err(Error::InvalidConfig { reason, .. }) => {
+                assert_eq!(
+                    reason,
+                    "only the first 120 characters fit in a macro's 240 inputs"
+                )
+            }
+            other => panic!("expected the text to be too long, got {other:?}"),
+        }
+
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_macro_from_unicode() -> Result<()> {
+        let ctrl_shift_u = Binding::new(Modifiers::CTRL | Modifiers::SHIFT, KeyCode::U);
+        assert_eq!(
+            Layout::Us.unicode_keystrokes('→'),
+            [
+                ctrl_shift_u,
+                KeyCode::Two.into(),
+                KeyCode::One.into(),
+                KeyCode::Nine.into(),
+                KeyCode::Two.into(),
+                KeyCode::Space.into(),
+            ]
+        );
+        // hex digits are typed like the layout types them
+        assert_eq!(
+            Layout::Fr.unicode_keystrokes('✓')[1..],
+            [
+                Binding::new(Modifiers::SHIFT, KeyCode::Two),
+                Binding::new(Modifiers::SHIFT, KeyCode::Seven),
+                Binding::new(Modifiers::SHIFT, KeyCode::One),
+                Binding::new(Modifiers::SHIFT, KeyCode::Three),
+                KeyCode::Space.into(),
+            ]
+        );
+
+        let step = |key_press, key_code| MacroData {
+            key_press,
+            delay: 0,
+            key_code,
+        };
+        assert_eq!(
+            Macro::from_unicode("a😀", Layout::Us)?.data,
+            [
+                step(KeyPress::Down, KeyCode::A),
+                step(KeyPress::Up, KeyCode::A),
+                step(KeyPress::Down, KeyCode::Leftctrl),
+                step(KeyPress::Down, KeyCode::Leftshift),
+                step(KeyPress::Down, KeyCode::U),
+                step(KeyPress::Up, KeyCode::U),
+                step(KeyPress::Up, KeyCode::Leftshift),
+                step(KeyPress::Up, KeyCode::Leftctrl),
+                step(KeyPress::Down, KeyCode::One),
+                step(KeyPress::Up, KeyCode::One),
+                step(KeyPress::Down, KeyCode::F),
+                step(KeyPress::Up, KeyCode::F),
+                step(KeyPress::Down, KeyCode::Six),
+                step(KeyPress::Up, KeyCode::Six),
+                step(KeyPress::Down, KeyCode::Zero),
+                step(KeyPress::Up, KeyCode::Zero),
+                step(KeyPress::Down, KeyCode::Zero),
+                step(KeyPress::Up, KeyCode::Zero),
+                step(KeyPress::Down, KeyCode::Space),
+                step(KeyPress::Up, KeyCode::Space),
+            ]
+        );
+
+        // what the layout types itself doesn't go through the input method
+        assert_eq!(
+            Macro::from_unicode("Grüße", Layout::De)?,
+            Macro::from_text("Grüße", Layout::De)?
+        );
+
+        // 16 inputs per arrow, and 18 per emoji
+        let arrows = "→".repeat(20);
+        assert_eq!(Layout::Us.unicode_capacity(&arrows), 15);
+        assert_eq!(Layout::Us.unicode_capacity("😀😀"), 2);
+        assert_eq!(Layout::Us.unicode_capacity(&"😀".repeat(14)), 13);
+        assert_eq!(
+            Macro::from_unicode(&arrows[..15 * 3], Layout::Us)?
+                .data
+                .len(),
+            MAX_INPUTS
+        );
+        match Macro::from_unicode(&arrows, Layout::Us) {
+            Err(Error::InvalidConfig { reason, .. }) => {
+                assert_eq!(
+                    reason,
+                    "only the first 15 characters fit in a macro's 240 inputs"
+                )
+            }
+            other => panic!("expected the text to be too long, got {other:?}"),
+        }
+
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_recorder() -> Result<()> {
+        const KEY_ESC: u16 = 1;
+        const KEY_ENTER: u16 = 28;
+        const KEY_LEFTCTRL: u16 = 29;
+        const KEY_A: u16 = 30;
+        const KEY_C: u16 = 46;
+        const KEY_FN: u16 = 464;
+
+        let at = |ms: u64| {
+            std::time::Duration::from_secs(1_000)
+                + std::time::Duration::from_micros(123_456 + ms * 100)
+        };
+        let syn = |ms| EvdevEvent {
+            time: at(ms),
+            kind: 0,
+            code: 0,
+            value: 0,
+        };
+        let stream = |events: &[EvdevEvent]| {
+            events
+                .iter()
+                .flat_map(|event| event.to_bytes())
+                .collect::<Vec<_>>()
+        };
+        let step = |key_press, delay, key_code| MacroData {
+            key_press,
+            delay,
+            key_code,
+        };
+
+        let event = EvdevEvent::key(at(10), KEY_C, 1);
+        assert_eq!(event.to_bytes().len(), EvdevEvent::SIZE);
+        assert_eq!(EvdevEvent::from_bytes(&event.to_bytes()), event);
+
+        // times in tenths of a millisecond
+        let events = stream(&[
+            // the release of the key that started recording
+            EvdevEvent::key(at(0), KEY_ENTER, 0),
+            syn(0),
+            EvdevEvent::key(at(1_000), KEY_LEFTCTRL, 1),
+            syn(1_000),
+            EvdevEvent::key(at(1_234), KEY_C, 1),
+            syn(1_234),
+            EvdevEvent::key(at(6_000), KEY_C, 2),
+            EvdevEvent::key(at(6_504), KEY_C, 0),
+            EvdevEvent::key(at(6_600), KEY_FN, 1),
+            EvdevEvent::key(at(7_000), KEY_ESC, 1),
+            EvdevEvent::key(at(8_000), KEY_LEFTCTRL, 0),
+        ]);
+
+        let mut recorder = Recorder::new()
+            .repetition(Repetition::WhilePressed)
+            .stop_key(KeyCode::Esc);
+        recorder.record(events.as_slice())?;
+        assert!(recorder.is_stopped() && !recorder.is_full());
+        assert_eq!(recorder.skipped(), [KEY_FN]);
+        // delays round the time since the first press, 23.4ms then 550.4ms
+        assert_eq!(
+            recorder.finish(),
+            Macro {
+                repetition: Repetition::WhilePressed,
+                data: vec![
+                    step(KeyPress::Down, 0, KeyCode::Leftctrl),
+                    step(KeyPress::Down, 2, KeyCode::C),
+                    step(KeyPress::Up, 53, KeyCode::C),
+                    step(KeyPress::Up, 0, KeyCode::Leftctrl),
+                ],
+            }
+        );
+
+        // a stream ending partway through a record, with a pause too long for a single input
+        let mut events = stream(&[
+            EvdevEvent::key(at(0), KEY_A, 1),
+            EvdevEvent::key(at(4_000_000), KEY_A, 0),
+        ]);
+        events.extend_from_slice(&EvdevEvent::key(at(4_000_001), KEY_A, 1).to_bytes()[..5]);
+        let mut recorder = Recorder::new();
+        recorder.record(events.as_slice())?;
+        assert!(!recorder.is_stopped());
+        assert_eq!(
+            recorder.data(),
+            [
+                step(KeyPress::Down, 0, KeyCode::A),
+                step(KeyPress::Up, 0x7FFF, KeyCode::A),
+            ]
+        );
+
+        // recording stops once the macro is full, leaving room to release what is held
+        let mut recorder = Recorder::new();
+        recorder.push(&EvdevEvent::key(at(0), KEY_LEFTCTRL, 1))?;
+        for tap in 0..200 {
+            recorder.push(&EvdevEvent::key(at(tap), KEY_A, 1))?;
+            recorder.push(&EvdevEvent::key(at(tap), KEY_A, 0))?;
+        }
+        assert!(recorder.is_stopped() && recorder.is_full());
+        let r#macro = recorder.finish();
+        assert_eq!(r#macro.data.len(), MAX_INPUTS);
+        assert_eq!(r#macro.data[238], step(KeyPress::Up, 0, KeyCode::A));
+        assert_eq!(r#macro.data[239], step(KeyPress::Up, 0, KeyCode::Leftctrl));
+
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_import_logs() -> Result<()> {
+        use std::time::Duration;
+
+        let evtest = "\
+Input driver version is 1.0.1
+Input device ID: bus 0x3 vendor 0x4d9 product 0xa1cd version 0x111
+Input device name: \"Falcon-8\"
+Testing ... (interrupt to exit)
+Event: time 1700000000.000000, type 1 (EV_KEY), code 28 (KEY_ENTER), value 0
+Event: time 1700000000.000000, -------------- SYN_REPORT ------------
+Event: time 1700000001.250000, type 4 (EV_MSC), code 4 (MSC_SCAN), value 700e0
+Event: time 1700000001.250000, type 1 (EV_KEY), code 29 (KEY_LEFTCTRL), value 1
+Event: time 1700000001.250000, -------------- SYN_REPORT ------------
+Event: time 1700000001.312000, type 1 (EV_KEY), code 46 (KEY_C), value 1
+Event: time 1700000001.812000, type 1 (EV_KEY), code 46 (KEY_C), value 2
+Event: time 1700000001.900000, type 1 (EV_KEY), code 46 (KEY_C), value 0
+Event: time 1700000004.000000, type 1 (EV_KEY), code 29 (KEY_LEFTCTRL), value 0
+";
+        let libinput = "\
+version: 1
+ndevices: 1
+libinput:
+  version: \"1.22.1\"
+  git: \"unknown\"
+system:
+  os: \"fedora:39\"
+  kernel: \"6.5.6\"
+  dmi: \"dmi:bvnLENOVO\"
+devices:
+- node: /dev/input/event7
+  evdev:
+    # Name: Falcon-8
+    # ID: bus 0x3 vendor 0x4d9 product 0xa1cd version 0x111
+    name: \"Falcon-8\"
+    id: [3, 1241, 41421, 273]
+    codes:
+      0: [0, 1, 2, 3, 4] # EV_SYN
+      1: [1, 2, 3, 4, 5] # EV_KEY
+    properties: []
+  hid: [
+    0x05, 0x01, 0x09, 0x06, 0xa1, 0x01
+  ]
+  udev:
+    properties:
+    - ID_INPUT=1
+    - ID_INPUT_KEYBOARD=1
+  quirks:
+  events:
+  # Current time is 10:00:00
+  - evdev:
+    - [  0,      0,   4,   4,  458976] # EV_MSC / MSC_SCAN             458976
+    - [  0,      0,   1,  29,       1] # EV_KEY / KEY_LEFTCTRL              1
+    - [  0,      0,   0,   0,       0] # ------------ SYN_REPORT (0) ---------- +0ms
+  - evdev:
+    - [  0,  62000,   1,  46,       1] # EV_KEY / KEY_C                     1
+    - [  0,  62000,   0,   0,       0] # ------------ SYN_REPORT (0) ---------- +62ms
+  - evdev:
+    - [  0, 650000,   1,  46,       0] # EV_KEY / KEY_C                     0
+    - [  0, 650000,   0,   0,       0] # ------------ SYN_REPORT (0) ---------- +588ms
+  - evdev:
+    - [  2, 750000,   1,  29,       0] # EV_KEY / KEY_LEFTCTRL              0
+    - [  2, 750000,   0,   0,       0] # ------------ SYN_REPORT (0) ---------- +2100ms
+";
+
+        let step = |key_press, delay, key_code| MacroData {
+            key_press,
+            delay,
+            key_code,
+        };
+        let import = |log: &str, recorder: Recorder| -> Result<Vec<MacroData>> {
+            let mut recorder = recorder;
+            recorder.record_events(EvdevEvent::from_log(log)?)?;
+            Ok(recorder.finish().data)
+        };
+
+        let events = EvdevEvent::from_evtest(evtest)?;
+        assert_eq!(events.len(), 7);
+        assert_eq!(
+            events[2],
+            EvdevEvent::key(Duration::new(1_700_000_001, 250_000_000), 29, 1)
+        );
+        assert_eq!(EvdevEvent::from_libinput_record(libinput)?.len(), 9);
+
+        // both logs hold the same presses
+        let copy = [
+            step(KeyPress::Down, 0, KeyCode::Leftctrl),
+            step(KeyPress::Down, 6, KeyCode::C),
+            step(KeyPress::Up, 59, KeyCode::C),
+            step(KeyPress::Up, 210, KeyCode::Leftctrl),
+        ];
+        assert_eq!(import(evtest, Recorder::new())?, copy);
+        assert_eq!(import(libinput, Recorder::new())?, copy);
+
+        let delays = |data: Vec<MacroData>| data.iter().map(|data| data.delay).collect::<Vec<_>>();
+        assert_eq!(
+            delays(import(evtest, Recorder::new().drop_delays())?),
+            [0, 0, 0, 0]
+        );
+        assert_eq!(
+            delays(import(
+                evtest,
+                Recorder::new().max_delay(Duration::from_millis(505))
+            )?),
+            [0, 6, 50, 50]
+        );
+        assert_eq!(
+            delays(import(libinput, Recorder::new().time_scale(0.5)?)?),
+            [0, 3, 30, 105]
+        );
+        assert_eq!(
+            delays(import(
+                libinput,
+                Recorder::new()
+                    .time_scale(2.0)?
+                    .max_delay(Duration::from_secs(1))
+            )?),
+            [0, 12, 100, 100]
+        );
+        assert!(matches!(
+            import(libinput, Recorder::new().time_scale(1e300)?),
+            Err(Error::InvalidConfig { .. })
+        ));
+        assert!(matches!(
+            Recorder::new().time_scale(-1.0),
+            Err(Error::InvalidConfig { .. })
+        ));
+        assert!(matches!(
+            Recorder::new().time_scale(f64::NAN),
+            Err(Error::InvalidConfig { .. })
+        ));
+
+        let syntax_error = |result: Result<Vec<EvdevEvent>>| match result {
+            Err(Error::Syntax { line, column, .. }) => (line, column),
+            other => panic!("expected a syntax error, got {other:?}"),
+        };
+        assert_eq!(
+            syntax_error(EvdevEvent::from_evtest(
+                "Event: time 1.000000, type 1 (EV_KEY), code KEY_A, value 1"
+            )),
+            (1, 40)
+        );
+        assert_eq!(
+            syntax_error(EvdevEvent::from_evtest("Event: time soon, type 1")),
+            (1, 13)
+        );
+        assert_eq!(
+            syntax_error(EvdevEvent::from_libinput_record(
+                "  events:\n  - evdev:\n    - [0, 0, 1, 30]"
+            )),
+            (3, 5)
+        );
+        assert!(EvdevEvent::from_log("hello").is_err());
+
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_key_geometry() {
+        for key in Key::ALL {
+            let (row, column) = key.position();
+            assert_eq!(Key::at(row, column), Some(key));
+            assert_eq!(key.rotated().rotated(), key);
+        }
+
+        assert_eq!(Key::Six.position(), (1, 1));
+        assert_eq!(Key::at(0, 3), Some(Key::Four));
+        assert_eq!(Key::at(2, 0), None);
+        assert_eq!(Key::at(0, 4), None);
+
+        assert_eq!(
+            Key::Six.neighbours().collect::<Vec<_>>(),
+            [Key::Two, Key::Five, Key::Seven]
+        );
+        assert_eq!(
+            Key::Four.neighbours().collect::<Vec<_>>(),
+            [Key::Eight, Key::Three]
+        );
+
+        assert_eq!(
+            Key::row(1).collect::<Vec<_>>(),
+            [Key::Five, Key::Six, Key::Seven, Key::Eight]
+        );
+        assert_eq!(Key::column(2).collect::<Vec<_>>(), [Key::Three, Key::Seven]);
+        assert_eq!(Key::row(2).count(), 0);
+        assert_eq!(Key::column(4).count(), 0);
+
+        assert_eq!(Key::One.rotated(), Key::Eight);
+        assert_eq!(Key::Two.rotated(), Key::Seven);
+        assert_eq!(Key::Five.rotated(), Key::Four);
+    }
+
+    #[test_log::test]
+    fn test_animation() -> Result<()> {
+        use std::time::Duration;
+
+        let red = Color::new(0xFF, 0, 0);
+
+        let mut rainbow = Rainbow::new(Duration::from_secs(4)).spread(0.5);
+        let frame = rainbow.render(Duration::ZERO);
+        assert_eq!(frame[Key::One as usize], red);
+        assert_eq!(frame[Key::Five as usize], red);
+        assert_eq!(frame[Key::Four as usize], Color::new(0, 0xFF, 0xFF));
+        let frame = rainbow.render(Duration::from_secs(1));
+        assert_eq!(frame[Key::One as usize], Color::new(0x80, 0xFF, 0));
+        let mut rainbow = rainbow.flow(Flow::Constant);
+        assert_eq!(rainbow.render(Duration::ZERO), [red; 8]);
+
+        let mut gradient = Gradient::new(Color::BLACK, Color::WHITE);
+        let frame = gradient.render(Duration::ZERO);
+        assert_eq!(frame[Key::One as usize], Color::BLACK);
+        assert_eq!(frame[Key::Two as usize], Color::new(0x55, 0x55, 0x55));
+        assert_eq!(frame[Key::Eight as usize], Color::WHITE);
+        let frame = gradient.flow(Flow::TopToBottom).render(Duration::ZERO);
+        assert_eq!(frame[Key::Four as usize], Color::BLACK);
+        assert_eq!(frame[Key::Five as usize], Color::WHITE);
+
+        let mut progress = ProgressBar::new(Color::WHITE, Color::BLACK);
+        progress.set_progress(0.5625);
+        let frame = progress.render(Duration::ZERO);
+        assert_eq!(frame[..4], [Color::WHITE; 4]);
+        assert_eq!(frame[Key::Five as usize], Color::new(0x80, 0x80, 0x80));
+        assert_eq!(frame[5..], [Color::BLACK; 3]);
+        progress.set_progress(2.0);
+        assert_eq!(progress.render(Duration::ZERO), [Color::WHITE; 8]);
+
+        let mut falcon8 = Falcon8::with_transport(Simulator::new());
+        let mut animation = Animation::with_clock(Ripple::new(red), ManualClock::new());
+
+        // the ring starts on the pressed key and reaches its neighbours an eighth of a second later
+        animation.handle_event(&InputEvent::Key {
+            key: Some(Key::One),
+            key_code: KeyCode::A,
+            press: KeyPress::Down,
+        });
+        animation.handle_event(&InputEvent::Key {
+            key: Some(Key::Eight),
+            key_code: KeyCode::A,
+            press: KeyPress::Up,
+        });
+        let frame = animation.step(&mut falcon8)?;
+        assert_eq!(frame[Key::One as usize], red);
+        assert_eq!(frame[Key::Two as usize], Color::BLACK);
+        animation.clock_mut().advance(Duration::from_millis(125));
+        let frame = animation.render();
+        assert_eq!(frame[Key::One as usize], Color::BLACK);
+        assert_eq!(frame[Key::Two as usize], Color::new(0xCA, 0, 0));
+        assert_eq!(frame[Key::Five as usize], Color::new(0xCA, 0, 0));
+        animation.clock_mut().advance(Duration::from_secs(1));
+        assert_eq!(animation.render(), [Color::BLACK; 8]);
+
+        let mut animation =
+            Animation::with_clock(Gradient::new(red, Color::WHITE), ManualClock::new())
+                .frame_rate(10);
+        falcon8.transport.clear_log();
+        animation.run_for(&mut falcon8, Duration::from_secs(1))?;
+
+        // the keys are read once, then every frame is a single write
+        let modes = falcon8.transport.sent_modes();
+        assert_eq!(modes[0], Mode::KeyRead);
+        assert_eq!(modes[1..], [Mode::KeyWrite; 10]);
+        assert_eq!(animation.clock().now(), Duration::from_millis(900));
+
+        let decoded = falcon8.transport.layer(Layer::One).decode()?;
+        assert_eq!(decoded.led_mode, LEDMode::Custom);
+        assert_eq!(decoded.key_colors[Key::One as usize], red);
+        assert_eq!(decoded.key_colors[Key::Four as usize], Color::WHITE);
+        assert_eq!(falcon8.led_controls.mode, Some(LEDMode::Custom));
+
+        // a real pad can't take more than a report every 50ms
+        struct Paced;
+
+        impl Transport for Paced {
+            fn set_report(&mut self, _report: &Report) -> Result<()> {
+                Ok(())
+            }
+
+            fn get_report(&mut self, _report: &mut Report) -> Result<()> {
+                Ok(())
+            }
+
+            fn report_delay(&self) -> Duration {
+                REPORT_DELAY
+            }
+        }
+
+        let animation = animation.frame_rate(60);
+        assert_eq!(
+            animation.frame_interval(&falcon8),
+            Duration::from_secs(1) / 60
+        );
+        assert_eq!(
+            animation.frame_interval(&Falcon8::with_transport(Paced)),
+            REPORT_DELAY
+        );
+
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_decode_round_trip() {
+        let report = Report::default();
+        let decoded = report.decode().unwrap();
+        assert_eq!(decoded.key_codes[Key::One as usize], KeyCode::Mute);
+        assert_eq!(decoded.brightness, Brightness::Max);
+        assert_eq!(decoded.encode(), report);
+
+        let mut changed = decoded;
+        changed.key_codes[Key::Eight as usize] = KeyCode::A;
+        let encoded = changed.encode();
+        assert_eq!(encoded[Key::Eight.to_index()], KeyCode::A as u8);
+        assert_eq!(encoded[0xA0..], report[0xA0..]);
+    }
+
+    #[test_log::test]
+    fn test_unknown_value_in_report() -> Result<()> {
+        let mut falcon = simulated();
+        let mut report = Report::default();
+        report[0x85] = 0x08;
+        falcon.set_report(&report)?;
+
+        let err = falcon.get_keys()?.decode().unwrap_err();
+        assert_eq!(
+            err,
+            DecodeError {
+                field: "LED mode",
+                offset: 0x85,
+                value: 0x08
+            }
+        );
+        Ok(())
     }
 }
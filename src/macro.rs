@@ -1,10 +1,20 @@
 use num_derive::FromPrimitive;
-use rusb::{Result, UsbContext};
+use num_traits::FromPrimitive as _;
 
-use crate::{Falcon8, KeyCode, Mode, Report};
+use crate::{DecodeError, Error, Falcon8, Key, KeyCode, Layer, Mode, Report, Result, Transport};
+
+/// Number of [`Mode::MacroWrite`] frames a macro is split across
+pub const MACRO_FRAMES: usize = 3;
+
+/// Most inputs a macro can hold
+pub const MAX_INPUTS: usize = 240;
+
+/// Longest delay an input can have, in steps of 10ms, as the top bit holds the [`KeyPress`]
+pub const MAX_DELAY: u16 = 0x7FFF;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, FromPrimitive)]
 #[repr(u16)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Repetition {
     UntilNextKeyPressed = 0x0000,
     WhilePressed = 0xFFFF,
@@ -12,6 +22,8 @@ pub enum Repetition {
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, FromPrimitive)]
 #[repr(u16)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum KeyPress {
     Up = 0x0000,
     Down = 0x8000,
@@ -21,9 +33,14 @@ impl Repetition {
     pub fn as_bytes(&self) -> [u8; 2] {
         [(*self as u16 >> 8) as u8, (*self as u16 & 0xff) as u8]
     }
+
+    pub fn from_bytes(bytes: [u8; 2]) -> Option<Self> {
+        Self::from_u16(u16::from_be_bytes(bytes))
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MacroData {
     pub key_press: KeyPress,
     pub delay: u16,
@@ -31,11 +48,18 @@ pub struct MacroData {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Macro {
     pub repetition: Repetition,
     pub data: Vec<MacroData>,
 }
 
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MacroControls {
+    pub macros: [Option<Macro>; 8],
+}
+
 impl MacroData {
     pub fn as_bytes(&self) -> [u8; 3] {
         //  0 0 0 0 0 0 0 0  0 0 0 0 0 0 0 0  0 0 0 0 0 0 0 0 0 <- HID Key Code
@@ -50,13 +74,42 @@ impl MacroData {
             self.key_code as u8,
         ]
     }
+
+    /// Inverse of [`MacroData::as_bytes`], the error offset being relative to `bytes`.
+    pub fn from_bytes(bytes: [u8; 3]) -> Result<Self, DecodeError> {
+        let key_code = KeyCode::from_u8(bytes[2]).ok_or(DecodeError {
+            field: "macro key code",
+            offset: 2,
+            value: bytes[2],
+        })?;
+
+        Ok(Self {
+            key_press: if bytes[0] & 0x80 != 0 {
+                KeyPress::Down
+            } else {
+                KeyPress::Up
+            },
+            delay: u16::from_be_bytes([bytes[0] & 0x7F, bytes[1]]),
+            key_code,
+        })
+    }
 }
 
 impl Macro {
-    /// Max limit of 240 inputs for any macro
+    pub fn new(repetition: Repetition) -> Self {
+        Self {
+            repetition,
+            data: Vec::new(),
+        }
+    }
+
+    /// Max limit of [`MAX_INPUTS`] inputs for any macro
     pub fn add_macro_data(&mut self, macro_data: MacroData) -> Result<()> {
-        if self.data.len() >= 240 {
-            return Err(rusb::Error::InvalidParam);
+        if self.data.len() >= MAX_INPUTS {
+            return Err(Error::invalid_config(
+                "macro",
+                format!("a macro can hold at most {MAX_INPUTS} inputs"),
+            ));
         }
 
         self.data.push(macro_data);
@@ -64,6 +117,28 @@ impl Macro {
         Ok(())
     }
 
+    /// Checks that the pad can hold the macro, which [`Macro::data`] being public doesn't ensure.
+    pub fn validate(&self) -> Result<()> {
+        if self.data.len() > MAX_INPUTS {
+            return Err(Error::invalid_config(
+                "macro",
+                format!(
+                    "a macro can hold at most {MAX_INPUTS} inputs, not {}",
+                    self.data.len()
+                ),
+            ));
+        }
+
+        if let Some(data) = self.data.iter().find(|data| data.delay > MAX_DELAY) {
+            return Err(Error::invalid_config(
+                "macro delay",
+                format!("{} is longer than {MAX_DELAY} steps of 10ms", data.delay),
+            ));
+        }
+
+        Ok(())
+    }
+
     /// Take each input in the macro and convert it to its byte array, first 2 bytes are the repetition then each input represented as 3 bytes.
     //
     /// The falcon 8 has 3 packets sending the macro, each packet is 264 bytes but has an 8 byte header so there are 256 bytes left effectively.
@@ -77,42 +152,168 @@ impl Macro {
 
         b[index].extend_from_slice(&self.repetition.as_bytes());
 
-        for macro_data in &self.data {
-            let bytes = macro_data.as_bytes();
-
+        for byte in self.data.iter().flat_map(MacroData::as_bytes) {
             if b[index].len() >= 256 {
                 index += 1;
+                // what doesn't fit is left out, see Macro::validate
+                if index == b.len() {
+                    break;
+                }
             }
-            b[index].push(bytes[0]);
+            b[index].push(byte);
+        }
 
-            if b[index].len() >= 256 {
-                index += 1;
+        b
+    }
+}
+
+/// Header shared by every macro frame: report id, mode, layer, macro slot of the key and frame
+/// number, padded to 8 bytes.
+fn macro_frame(mode: Mode, layer: Layer, key: Key, index: usize) -> Report {
+    let mut frame = Report::empty().clear(Some(layer));
+    frame.set_mode(mode);
+    frame[3] = key.macro_slot();
+    frame[4] = index as u8;
+    frame
+}
+
+/// The [`Mode::MacroWrite`] frames uploading every macro to its key on `layer`, one macro after
+/// the other. Fails before building any if one of the macros doesn't fit the pad.
+pub(crate) fn macro_frames(layer: Layer, macros: &[(Key, Macro)]) -> Result<Vec<Report>> {
+    let mut frames = Vec::new();
+    for (key, r#macro) in macros {
+        frames.extend(r#macro.to_frames(layer, *key)?);
+    }
+
+    Ok(frames)
+}
+
+impl Macro {
+    /// Builds the [`Mode::MacroWrite`] frames uploading this macro to `key` on `layer`.
+    ///
+    /// Each frame carries an 8 byte header (report id, mode, layer, macro slot of the key, frame
+    /// number) followed by one of the 256 byte chunks from [`Macro::to_bytes`]. Fails if the
+    /// macro doesn't pass [`Macro::validate`].
+    pub fn to_frames(&self, layer: Layer, key: Key) -> Result<[Report; MACRO_FRAMES]> {
+        self.validate()?;
+        let chunks = self.to_bytes();
+
+        Ok(std::array::from_fn(|index| {
+            let mut frame = macro_frame(Mode::MacroWrite, layer, key, index);
+            frame.as_bytes_mut()[8..8 + chunks[index].len()].copy_from_slice(&chunks[index]);
+            frame
+        }))
+    }
+
+    /// Inverse of [`Macro::to_frames`]. The inputs end at the first all-zero entry, and error
+    /// offsets count bytes across the frames as if they were laid out back to back.
+    pub fn from_frames(frames: &[Report; MACRO_FRAMES]) -> Result<Self, DecodeError> {
+        // offset of the nth payload byte within the frames
+        let offset = |n: usize| (n / 256) * 264 + 8 + n % 256;
+        let payload = frames
+            .iter()
+            .flat_map(|frame| frame[8..].iter().copied())
+            .collect::<Vec<_>>();
+
+        let repetition = Repetition::from_bytes([payload[0], payload[1]]).ok_or_else(|| {
+            let n = if matches!(payload[0], 0x00 | 0xFF) {
+                1
+            } else {
+                0
+            };
+            DecodeError {
+                field: "macro repetition",
+                offset: offset(n),
+                value: payload[n],
             }
-            b[index].push(bytes[1]);
+        })?;
 
-            if b[index].len() >= 256 {
-                index += 1;
+        let mut r#macro = Macro::new(repetition);
+        for (i, input) in payload[2..].chunks_exact(3).enumerate() {
+            if input == [0; 3] {
+                break;
             }
-            b[index].push(bytes[2]);
+
+            let data = MacroData::from_bytes([input[0], input[1], input[2]]).map_err(|err| {
+                DecodeError {
+                    offset: offset(2 + i * 3 + err.offset),
+                    ..err
+                }
+            })?;
+            r#macro.data.push(data);
         }
 
-        b
+        Ok(r#macro)
     }
 }
 
-impl<T: UsbContext> Falcon8<T> {
+impl MacroControls {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_macro(&mut self, key: Key, r#macro: Macro) -> &mut Self {
+        self.macros[key as usize] = Some(r#macro);
+        self
+    }
+
+    pub fn clear_macro(&mut self, key: Key) -> &mut Self {
+        self.macros[key as usize] = None;
+        self
+    }
+}
+
+impl<T: Transport> Falcon8<T> {
+    /// Uploads every macro in [`Falcon8::macro_controls`] to the active layer.
     pub fn update_macros(&mut self) -> Result<()> {
-        let mut report = Report::from_falcon(self);
+        let layer = self.active_layer;
+        let macros = self
+            .macro_controls
+            .macros
+            .iter()
+            .zip(Key::ALL)
+            .filter_map(|(r#macro, key)| Some((key, r#macro.clone()?)))
+            .collect::<Vec<_>>();
+
+        self.write_macros(layer, &macros)
+    }
+
+    /// Assigns `macro` to `key` on `layer` and uploads it, leaving the other keys alone.
+    pub fn upload_macro(&mut self, layer: Layer, key: Key, r#macro: &Macro) -> Result<()> {
+        self.write_macros(layer, &[(key, r#macro.clone())])
+    }
+
+    /// Downloads the macro stored for `key` on `layer`, which has no inputs if none was set.
+    pub fn read_macro(&mut self, layer: Layer, key: Key) -> Result<Macro> {
+        let mut frames = [Report::new(); MACRO_FRAMES];
+
+        for (index, frame) in frames.iter_mut().enumerate() {
+            self.set_report(&macro_frame(Mode::MacroRead, layer, key, index))?;
+            self.get_report(frame)?;
+        }
+
+        Ok(Macro::from_frames(&frames)?)
+    }
+
+    fn write_macros(&mut self, layer: Layer, macros: &[(Key, Macro)]) -> Result<()> {
+        let frames = macro_frames(layer, macros)?;
+        let mut report = Report::empty().clear(Some(layer));
 
         self.set_report(&report)?;
 
         self.get_report(&mut report)?;
 
         report.set_mode(Mode::KeyWrite).clear_end();
+        for (key, _) in macros {
+            report.set_macro(*key, key.macro_slot());
+        }
         self.set_report(&report)?;
 
-        // send the 4 macro frames
+        // send the 3 macro frames of every macro, then commit them
+        for frame in frames {
+            self.set_report(&frame)?;
+        }
 
-        Ok(())
+        self.finalize()
     }
 }
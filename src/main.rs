@@ -0,0 +1,618 @@
+use std::{path::PathBuf, process::ExitCode, time::Duration};
+
+use clap::{Args, Parser, Subcommand};
+use num_traits::FromPrimitive;
+use serde::de::{DeserializeOwned, IntoDeserializer};
+
+use falcon8::{
+    profile::{MacroProfile, Profile},
+    Binding, Brightness, Color, DeviceSelector, Error, EvdevEvent, Falcon8, Flow, Key, KeyCode,
+    LEDMode, Layer, Layout, Macro, Recorder, Report, Result, Transport,
+};
+
+/// Configure Falcon-8 macro pads
+#[derive(Debug, Parser)]
+#[command(name = "falcon8", version)]
+struct Cli {
+    #[command(flatten)]
+    select: Select,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Filters picking the pad to use when several are connected
+#[derive(Debug, Args)]
+struct Select {
+    /// USB bus number of the pad
+    #[arg(long, global = true)]
+    bus: Option<u8>,
+    /// USB address of the pad
+    #[arg(long, global = true)]
+    address: Option<u8>,
+    /// Hub ports leading to the pad, as printed by `falcon8 list`, e.g. `3-2.1`
+    #[arg(long, global = true, value_parser = parse_port)]
+    port: Option<(Option<u8>, Vec<u8>)>,
+    /// Serial number of the pad
+    #[arg(long, global = true)]
+    serial: Option<String>,
+    /// Talk to the pad through /dev/hidraw*, which keeps it typing meanwhile. Only --serial can
+    /// pick the pad then
+    #[cfg(target_os = "linux")]
+    #[arg(long, global = true, conflicts_with_all = ["bus", "address", "port"])]
+    hidraw: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// List the connected pads, and why a pad wouldn't be selected
+    List,
+    /// Print the pad's USB descriptor strings
+    Info,
+    /// Switch the active layer
+    Layer {
+        #[command(subcommand)]
+        command: LayerCommand,
+    },
+    /// Read or change key bindings
+    Keys {
+        #[command(subcommand)]
+        command: KeysCommand,
+    },
+    /// Change the LED settings
+    Leds {
+        #[command(subcommand)]
+        command: LedsCommand,
+    },
+    /// Upload or download macros
+    Macro {
+        #[command(subcommand)]
+        command: MacroCommand,
+    },
+    /// Apply or dump TOML profiles
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommand,
+    },
+    /// Exchange raw feature reports
+    Raw {
+        #[command(subcommand)]
+        command: RawCommand,
+    },
+}
+
+#[derive(Debug, Args)]
+struct LayerArg {
+    /// Layer to work on, `one` to `five` or `1` to `5`
+    #[arg(short, long, value_parser = parse_layer, default_value = "one")]
+    layer: Layer,
+}
+
+#[derive(Debug, Subcommand)]
+enum LayerCommand {
+    /// Make LAYER the active layer
+    Set {
+        #[arg(value_parser = parse_layer)]
+        layer: Layer,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum KeysCommand {
+    /// Print the bindings of every key
+    Get {
+        #[command(flatten)]
+        layer: LayerArg,
+        /// Print the bindings laid out like the keys, two rows of four
+        #[arg(long)]
+        grid: bool,
+        /// Lay the grid out for a pad mounted upside down
+        #[arg(long, requires = "grid")]
+        upside_down: bool,
+    },
+    /// Bind keys, e.g. `one=KPAsterisk five=vol+ six=ctrl+shift+t`
+    Set {
+        #[command(flatten)]
+        layer: LayerArg,
+        #[arg(required = true, value_parser = parse_binding)]
+        bindings: Vec<(Key, Binding)>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum LedsCommand {
+    /// Change any of the LED settings
+    Set {
+        #[command(flatten)]
+        layer: LayerArg,
+        #[arg(long, value_parser = parse_name::<LEDMode>)]
+        mode: Option<LEDMode>,
+        #[arg(long, value_parser = parse_name::<Brightness>)]
+        brightness: Option<Brightness>,
+        #[arg(long, value_parser = parse_name::<Flow>)]
+        flow: Option<Flow>,
+        /// Color of every LED, e.g. `#FF8000`, `rgb(255, 128, 0)`, `hsv(30, 100%, 100%)` or `orange`.
+        /// Switches to Custom mode unless --mode is given
+        #[arg(long, value_parser = parse_color)]
+        color: Option<Color>,
+        /// Color of one key in Custom mode, as KEY=COLOR. Switches to Custom mode unless --mode is
+        /// given
+        #[arg(long = "key-color", value_parser = parse_key_color)]
+        key_colors: Vec<(Key, Color)>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum MacroCommand {
+    /// Upload the macro described by a TOML file (`repetition` and `steps`), or by a script in
+    /// any other file, to KEY
+    Upload {
+        #[command(flatten)]
+        layer: LayerArg,
+        #[arg(value_parser = parse_name::<Key>)]
+        key: Key,
+        file: PathBuf,
+    },
+    /// Print the macro of KEY as TOML, or write it to FILE
+    Download {
+        #[command(flatten)]
+        layer: LayerArg,
+        #[arg(value_parser = parse_name::<Key>)]
+        key: Key,
+        file: Option<PathBuf>,
+        /// Write the macro as a script instead of TOML
+        #[arg(long)]
+        script: bool,
+    },
+    /// Make KEY type TEXT, as a host with the given keyboard layout reads it
+    Type {
+        #[command(flatten)]
+        layer: LayerArg,
+        #[arg(value_parser = parse_name::<Key>)]
+        key: Key,
+        text: String,
+        /// Keyboard layout of the host: us, uk, de or fr
+        #[arg(long, default_value = "us")]
+        layout: Layout,
+        /// Enter characters the layout lacks with Ctrl+Shift+U, as GTK and IBus take them
+        #[arg(long)]
+        unicode: bool,
+    },
+    /// Upload the key presses of an evtest log or libinput recording to KEY, as a macro
+    Import {
+        #[command(flatten)]
+        layer: LayerArg,
+        #[arg(value_parser = parse_name::<Key>)]
+        key: Key,
+        file: PathBuf,
+        /// Play every input right after the one before
+        #[arg(long)]
+        no_delays: bool,
+        /// Shorten the delays longer than MS milliseconds
+        #[arg(long, value_name = "MS")]
+        max_delay: Option<u64>,
+        /// Multiply the time between inputs, e.g. 0.5 to play twice as fast
+        #[arg(long, default_value_t = 1.0, value_parser = parse_time_scale)]
+        time_scale: f64,
+    },
+    /// Record a macro from an input device like /dev/input/event3 until STOP is pressed, and
+    /// upload it to KEY
+    #[cfg(target_os = "linux")]
+    Record {
+        #[command(flatten)]
+        layer: LayerArg,
+        #[arg(value_parser = parse_name::<Key>)]
+        key: Key,
+        device: PathBuf,
+        /// Key ending the recording, which isn't recorded
+        #[arg(long, default_value = "esc")]
+        stop: KeyCode,
+        /// Keep the keys pressed while recording from reaching anything else
+        #[arg(long)]
+        grab: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ProfileCommand {
+    /// Apply a profile on top of the pad's configuration
+    Apply { file: PathBuf },
+    /// Print the pad's whole configuration as a profile, or write it to FILE
+    Dump { file: Option<PathBuf> },
+}
+
+#[derive(Debug, Subcommand)]
+enum RawCommand {
+    /// Request the key report of a layer and hex dump it
+    Get {
+        #[command(flatten)]
+        layer: LayerArg,
+    },
+    /// Send a report given as hex bytes, zero padded to 264 bytes
+    Set {
+        #[arg(required = true)]
+        bytes: Vec<String>,
+    },
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("falcon8: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    if cli.select.hidraw {
+        return run_hidraw(cli);
+    }
+
+    let selector = cli.select.selector();
+
+    if let Command::List = cli.command {
+        for candidate in selector.candidates()? {
+            match candidate.skipped {
+                Some(reason) => println!("{} ({reason})", candidate.info),
+                None => println!("{}", candidate.info),
+            }
+        }
+        return Ok(());
+    }
+
+    let mut falcon = selector.open()?;
+
+    match cli.command {
+        Command::List => unreachable!(),
+        Command::Info => falcon.print_device_info(),
+        command => configure(&mut falcon, command),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn run_hidraw(cli: Cli) -> Result<()> {
+    let mut nodes = falcon8::HidrawNode::find()?;
+    if let Some(serial) = &cli.select.serial {
+        nodes.retain(|node| node.uniq == *serial);
+    }
+
+    // an empty list isn't a failure, unlike having no pad to talk to
+    if nodes.is_empty() && !matches!(cli.command, Command::List) {
+        return Err(Error::DeviceNotFound);
+    }
+
+    match cli.command {
+        Command::List | Command::Info => {
+            for node in nodes {
+                println!("{node}");
+            }
+            Ok(())
+        }
+        _ if nodes.len() > 1 => Err(Error::AmbiguousHidraw { nodes }),
+        command => {
+            let transport = falcon8::HidrawTransport::open(&nodes[0].path)?;
+            configure(&mut Falcon8::with_transport(transport), command)
+        }
+    }
+}
+
+/// Runs the commands that work the same with any backend.
+fn configure<T: Transport>(falcon: &mut Falcon8<T>, command: Command) -> Result<()> {
+    match command {
+        Command::List | Command::Info => unreachable!(),
+        Command::Layer {
+            command: LayerCommand::Set { layer },
+        } => falcon.update_layer(layer),
+        Command::Keys { command } => match command {
+            KeysCommand::Get {
+                layer,
+                grid,
+                upside_down,
+            } => {
+                falcon.active_layer = layer.layer;
+                let report = falcon.get_keys()?;
+                let binding = |key: Key| {
+                    let byte = report[key.to_index()];
+                    match KeyCode::from_u8(byte) {
+                        Some(key_code) => format!("{key_code:?}"),
+                        None => format!("unknown (0x{byte:02X})"),
+                    }
+                };
+
+                if !grid {
+                    for key in Key::ALL {
+                        println!("{:<6} {}", name(&key), binding(key));
+                    }
+                    return Ok(());
+                }
+
+                for row in 0..Key::ROWS {
+                    let keys =
+                        Key::row(row).map(|key| if upside_down { key.rotated() } else { key });
+                    let cells = keys
+                        .map(|key| format!("{:<20}", format!("{}: {}", name(&key), binding(key))))
+                        .collect::<String>();
+                    println!("{}", cells.trim_end());
+                }
+                Ok(())
+            }
+            KeysCommand::Set { layer, bindings } => {
+                falcon.active_layer = layer.layer;
+                // disabled keys are left as they are on the pad
+                for key in Key::ALL {
+                    falcon.key_controls.set_key(key, KeyCode::Disable);
+                }
+                for (key, binding) in bindings {
+                    falcon.key_controls.set_binding(key, binding);
+                }
+                falcon.update_keys()
+            }
+        },
+        Command::Leds {
+            command:
+                LedsCommand::Set {
+                    layer,
+                    mode,
+                    brightness,
+                    flow,
+                    color,
+                    key_colors,
+                },
+        } => {
+            falcon.active_layer = layer.layer;
+            let controls = &mut falcon.led_controls;
+            // colors only show in Custom mode
+            let colored = color.is_some() || !key_colors.is_empty();
+            if let Some(mode) = mode.or(colored.then_some(LEDMode::Custom)) {
+                controls.set_mode(mode);
+            }
+            if let Some(brightness) = brightness {
+                controls.set_brightness(brightness);
+            }
+            if let Some(flow) = flow {
+                controls.set_flow(flow);
+            }
+            if let Some(color) = color {
+                controls.set_color(color);
+            }
+            for (key, color) in key_colors {
+                controls.set_key_color(key, color);
+            }
+            falcon.update_leds()?;
+            falcon.finalize()
+        }
+        Command::Macro { command } => match command {
+            MacroCommand::Upload { layer, key, file } => {
+                let text = std::fs::read_to_string(&file).map_err(Error::Io)?;
+                let r#macro = if file
+                    .extension()
+                    .is_some_and(|extension| extension == "toml")
+                {
+                    let macro_profile: MacroProfile =
+                        toml::from_str(&text).map_err(|err| Error::Profile(err.to_string()))?;
+                    macro_profile.to_macro()?
+                } else {
+                    text.parse()?
+                };
+                falcon.upload_macro(layer.layer, key, &r#macro)
+            }
+            MacroCommand::Download {
+                layer,
+                key,
+                file,
+                script,
+            } => {
+                let r#macro = falcon.read_macro(layer.layer, key)?;
+                let text = if script {
+                    format!("{}\n", r#macro)
+                } else {
+                    toml::to_string(&MacroProfile::from_macro(&r#macro))
+                        .map_err(|err| Error::Profile(err.to_string()))?
+                };
+                output(file, &text)
+            }
+            MacroCommand::Type {
+                layer,
+                key,
+                text,
+                layout,
+                unicode,
+            } => {
+                let r#macro = if unicode {
+                    Macro::from_unicode(&text, layout)?
+                } else {
+                    Macro::from_text(&text, layout)?
+                };
+                falcon.upload_macro(layer.layer, key, &r#macro)
+            }
+            MacroCommand::Import {
+                layer,
+                key,
+                file,
+                no_delays,
+                max_delay,
+                time_scale,
+            } => {
+                let log = std::fs::read_to_string(file).map_err(Error::Io)?;
+
+                let mut recorder = Recorder::new().time_scale(time_scale)?;
+                if let Some(max_delay) = max_delay {
+                    recorder = recorder.max_delay(Duration::from_millis(max_delay));
+                }
+                if no_delays {
+                    recorder = recorder.drop_delays();
+                }
+                recorder.record_events(EvdevEvent::from_log(&log)?)?;
+                for code in recorder.skipped() {
+                    eprintln!("falcon8: left out key {code}, which the pad can't send");
+                }
+                if recorder.is_full() {
+                    return Err(Error::InvalidConfig {
+                        field: "log",
+                        reason: format!(
+                            "the key presses after the first {} inputs don't fit in a macro",
+                            recorder.data().len()
+                        ),
+                    });
+                }
+
+                let r#macro = recorder.finish();
+                println!("{}", r#macro);
+                falcon.upload_macro(layer.layer, key, &r#macro)
+            }
+            #[cfg(target_os = "linux")]
+            MacroCommand::Record {
+                layer,
+                key,
+                device,
+                stop,
+                grab,
+            } => {
+                let device = falcon8::EvdevFile::open(device)?;
+                if grab {
+                    device.grab()?;
+                }
+
+                eprintln!("recording, press {stop:?} to stop");
+                let mut recorder = Recorder::new().stop_key(stop);
+                recorder.record(device)?;
+                for code in recorder.skipped() {
+                    eprintln!("falcon8: left out key {code}, which the pad can't send");
+                }
+                if recorder.is_full() {
+                    eprintln!("falcon8: the macro is full, recording stopped before {stop:?}");
+                }
+
+                let r#macro = recorder.finish();
+                println!("{}", r#macro);
+                falcon.upload_macro(layer.layer, key, &r#macro)
+            }
+        },
+        Command::Profile { command } => match command {
+            ProfileCommand::Apply { file } => falcon.apply_profile(&Profile::load(file)?),
+            ProfileCommand::Dump { file } => output(file, &falcon.dump_profile()?.to_toml()?),
+        },
+        Command::Raw { command } => match command {
+            RawCommand::Get { layer } => {
+                falcon.active_layer = layer.layer;
+                let report = falcon.get_keys()?;
+                for row in report.chunks(16) {
+                    let row = row.iter().map(|b| format!("{b:02x}")).collect::<Vec<_>>();
+                    println!("{}", row.join(" "));
+                }
+                Ok(())
+            }
+            RawCommand::Set { bytes } => {
+                let mut report = Report::new();
+                let bytes = bytes.iter().flat_map(|arg| arg.split_whitespace());
+                for (index, byte) in bytes.enumerate() {
+                    let invalid = |reason: String| Error::InvalidConfig {
+                        field: "raw report",
+                        reason,
+                    };
+                    if index >= report.len() {
+                        return Err(invalid(format!("more than {} bytes", report.len())));
+                    }
+                    report[index] = u8::from_str_radix(byte.trim_start_matches("0x"), 16)
+                        .map_err(|_| invalid(format!("{byte:?} is not a hex byte")))?;
+                }
+                falcon.set_report(&report)
+            }
+        },
+    }
+}
+
+impl Select {
+    fn selector(&self) -> DeviceSelector {
+        let mut selector = DeviceSelector::new();
+
+        if let Some((bus, port_path)) = &self.port {
+            if let Some(bus) = bus {
+                selector = selector.bus(*bus);
+            }
+            selector = selector.port_path(port_path.clone());
+        }
+        if let Some(bus) = self.bus {
+            selector = selector.bus(bus);
+        }
+        if let Some(address) = self.address {
+            selector = selector.address(address);
+        }
+        if let Some(serial) = &self.serial {
+            selector = selector.serial(serial);
+        }
+
+        selector
+    }
+}
+
+/// Writes `text` to `file`, or stdout without one.
+fn output(file: Option<PathBuf>, text: &str) -> Result<()> {
+    match file {
+        Some(file) => std::fs::write(file, text).map_err(Error::Io),
+        None => {
+            print!("{text}");
+            Ok(())
+        }
+    }
+}
+
+/// Name of a value as written in profiles.
+fn name<T: serde::Serialize>(value: &T) -> String {
+    toml::Value::try_from(value)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_owned))
+        .unwrap_or_default()
+}
+
+/// Parses a value by the name it has in profiles.
+fn parse_name<T: DeserializeOwned>(s: &str) -> std::result::Result<T, String> {
+    T::deserialize(s.into_deserializer()).map_err(|err: serde::de::value::Error| err.to_string())
+}
+
+fn parse_time_scale(s: &str) -> std::result::Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(scale) if scale.is_finite() && scale >= 0.0 => Ok(scale),
+        _ => Err(format!("{s:?} is negative or not a number")),
+    }
+}
+
+fn parse_layer(s: &str) -> std::result::Result<Layer, String> {
+    match s.parse::<u8>() {
+        Ok(number) => Layer::from_u8(number).ok_or_else(|| format!("no layer {number}")),
+        Err(_) => parse_name(s),
+    }
+}
+
+/// Parses `BUS-PORT.PORT...` or just `PORT.PORT...`.
+fn parse_port(s: &str) -> std::result::Result<(Option<u8>, Vec<u8>), String> {
+    let number = |n: &str| {
+        n.parse::<u8>()
+            .map_err(|_| format!("{n:?} is not a port number"))
+    };
+
+    let (bus, ports) = match s.split_once('-') {
+        Some((bus, ports)) => (Some(number(bus)?), ports),
+        None => (None, s),
+    };
+
+    Ok((bus, ports.split('.').map(number).collect::<Result<_, _>>()?))
+}
+
+fn parse_color(s: &str) -> std::result::Result<Color, String> {
+    s.parse().map_err(|err: Error| err.to_string())
+}
+
+fn parse_binding(s: &str) -> std::result::Result<(Key, Binding), String> {
+    let (key, binding) = s.split_once('=').ok_or("expected KEY=BINDING")?;
+    let binding = binding.parse().map_err(|err: Error| err.to_string())?;
+    Ok((parse_name(key)?, binding))
+}
+
+fn parse_key_color(s: &str) -> std::result::Result<(Key, Color), String> {
+    let (key, color) = s.split_once('=').ok_or("expected KEY=COLOR")?;
+    Ok((parse_name(key)?, parse_color(color)?))
+}
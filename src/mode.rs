@@ -1,4 +1,6 @@
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+use num_derive::FromPrimitive;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromPrimitive)]
 #[repr(u8)]
 pub enum Mode {
     LayerWrite = 0x01,
@@ -7,4 +9,7 @@ pub enum Mode {
     Finalize = 0x06,
 
     KeyRead = Mode::KeyWrite as u8 | 0x80,
+    /// Reads a macro's frames back. Unverified: this assumes the firmware answers the read bit on
+    /// macros like it does on keys, which only the [`crate::Simulator`] is known to do.
+    MacroRead = Mode::MacroWrite as u8 | 0x80,
 }
@@ -0,0 +1,291 @@
+//! Human-editable pad profiles, stored as TOML.
+//!
+//! A profile only lists what it wants to change, so it can be applied on top of whatever a pad
+//! currently holds. Layers and keys are named `one` to `five` and `one` to `eight`, key codes are
+//! anything [`KeyCode`] parses (its variant names, Linux `KEY_*` names or aliases like `ctrl`),
+//! LED settings use the [`LEDMode`], [`Brightness`] and [`Flow`] variant names, and colors can be
+//! written in any form [`Color`] parses, such as `#RRGGBB`, `hsv(120, 100%, 50%)` or `orange`.
+//! Dumped profiles always use `#RRGGBB`.
+//!
+//! ```toml
+//! [layers.one]
+//! led_mode = "Custom"
+//! brightness = "Max"
+//! flow = "Constant"
+//! color = "#FFFFFF"
+//!
+//! [layers.one.keys]
+//! one = "KPAsterisk"
+//! five = "KPSlash"
+//! six = "vol+"
+//!
+//! [layers.one.colors]
+//! one = "#FF0000"
+//! five = "lime"
+//!
+//! # a macro is a list of steps, each pressing or releasing a key after waiting `delay_ms`
+//! [layers.one.macros.three]
+//! repetition = "UntilNextKeyPressed"
+//! steps = [
+//!     { press = "down", key = "Leftctrl" },
+//!     { press = "down", key = "C", delay_ms = 20 },
+//!     { press = "up", key = "C", delay_ms = 20 },
+//!     { press = "up", key = "Leftctrl" },
+//! ]
+//!
+//! # or a script, see `Macro::from_str`
+//! [layers.one.macros.four]
+//! script = "down ctrl; wait 20ms; tap v; up ctrl"
+//! ```
+
+use std::{collections::BTreeMap, path::Path, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Brightness, Color, DeviceConfig, Error, Falcon8, Flow, Key, KeyCode, KeyPress, LEDMode, Layer,
+    LayerConfig, Macro, MacroData, Repetition, Result, Transport, MAX_DELAY,
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub layers: BTreeMap<Layer, LayerProfile>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayerProfile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub led_mode: Option<LEDMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<Brightness>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flow: Option<Flow>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<Color>,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<Key, KeyCode>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub colors: BTreeMap<Key, Color>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub macros: BTreeMap<Key, MacroProfile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MacroProfile {
+    #[serde(default = "default_repetition")]
+    pub repetition: Repetition,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<MacroStep>,
+    /// The macro written as a script instead of `steps`, see [`Macro::from_str`]. A `repeat`
+    /// in the script wins over `repetition`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MacroStep {
+    pub press: KeyPress,
+    pub key: KeyCode,
+    /// Time to wait before the step, in steps of 10ms
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub delay_ms: u32,
+}
+
+fn default_repetition() -> Repetition {
+    Repetition::UntilNextKeyPressed
+}
+
+fn is_zero(delay_ms: &u32) -> bool {
+    *delay_ms == 0
+}
+
+impl Profile {
+    /// Describes every setting of `config`, so applying the profile reproduces it exactly.
+    pub fn from_config(config: &DeviceConfig) -> Self {
+        let layers = Layer::ALL
+            .into_iter()
+            .map(|layer| (layer, LayerProfile::from_config(config.layer(layer))))
+            .collect();
+
+        Self { layers }
+    }
+
+    /// Overrides the settings of `config` that this profile mentions.
+    pub fn apply(&self, config: &mut DeviceConfig) -> Result<()> {
+        for (layer, layer_profile) in &self.layers {
+            layer_profile.apply(config.layer_mut(*layer))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        std::fs::read_to_string(path).map_err(Error::Io)?.parse()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, self.to_toml()?).map_err(Error::Io)
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).map_err(|err| Error::Profile(err.to_string()))
+    }
+}
+
+impl FromStr for Profile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        toml::from_str(s).map_err(|err| Error::Profile(err.to_string()))
+    }
+}
+
+impl LayerProfile {
+    pub fn from_config(config: &LayerConfig) -> Self {
+        let mut layer_profile = Self {
+            led_mode: Some(config.led_mode),
+            brightness: Some(config.brightness),
+            flow: Some(config.flow),
+            color: Some(config.led_color),
+            ..Default::default()
+        };
+
+        for key in Key::ALL {
+            layer_profile
+                .keys
+                .insert(key, config.key_codes[key as usize]);
+            layer_profile
+                .colors
+                .insert(key, config.key_colors[key as usize]);
+
+            if let Some(r#macro) = &config.macros[key as usize] {
+                layer_profile
+                    .macros
+                    .insert(key, MacroProfile::from_macro(r#macro));
+            }
+        }
+
+        layer_profile
+    }
+
+    pub fn apply(&self, config: &mut LayerConfig) -> Result<()> {
+        if let Some(led_mode) = self.led_mode {
+            config.led_mode = led_mode;
+        }
+
+        if let Some(brightness) = self.brightness {
+            config.brightness = brightness;
+        }
+
+        if let Some(flow) = self.flow {
+            config.flow = flow;
+        }
+
+        if let Some(color) = self.color {
+            config.led_color = color;
+        }
+
+        for (key, key_code) in &self.keys {
+            config.key_codes[*key as usize] = *key_code;
+        }
+
+        for (key, color) in &self.colors {
+            config.key_colors[*key as usize] = *color;
+        }
+
+        for (key, macro_profile) in &self.macros {
+            config.macros[*key as usize] = Some(macro_profile.to_macro()?);
+        }
+
+        Ok(())
+    }
+}
+
+impl MacroProfile {
+    pub fn from_macro(r#macro: &Macro) -> Self {
+        let steps = r#macro
+            .data
+            .iter()
+            .map(|data| MacroStep {
+                press: data.key_press,
+                key: data.key_code,
+                delay_ms: data.delay as u32 * 10,
+            })
+            .collect();
+
+        Self {
+            repetition: r#macro.repetition,
+            steps,
+            script: None,
+        }
+    }
+
+    pub fn to_macro(&self) -> Result<Macro> {
+        if let Some(script) = &self.script {
+            if !self.steps.is_empty() {
+                return Err(Error::invalid_config(
+                    "macro",
+                    "steps and script can't both be given",
+                ));
+            }
+
+            let (data, repetition) = crate::script::parse(script)?;
+            return Ok(Macro {
+                repetition: repetition.unwrap_or(self.repetition),
+                data,
+            });
+        }
+
+        let mut r#macro = Macro::new(self.repetition);
+
+        for step in &self.steps {
+            if step.delay_ms % 10 != 0 || step.delay_ms / 10 > MAX_DELAY as u32 {
+                return Err(Error::invalid_config(
+                    "macro step delay_ms",
+                    format!(
+                        "{} is not a multiple of 10ms up to {}ms",
+                        step.delay_ms,
+                        MAX_DELAY as u32 * 10
+                    ),
+                ));
+            }
+
+            r#macro.add_macro_data(MacroData {
+                key_press: step.press,
+                delay: (step.delay_ms / 10) as u16,
+                key_code: step.key,
+            })?;
+        }
+
+        Ok(r#macro)
+    }
+}
+
+impl<T: Transport> Falcon8<T> {
+    /// Applies `profile` on top of the pad's current configuration.
+    ///
+    /// Reading macros back relies on [`crate::Mode::MacroRead`], which isn't verified on hardware,
+    /// so the current macros are only kept when they read back. A key whose macro doesn't read back
+    /// loses it, unless `profile` sets one.
+    pub fn apply_profile(&mut self, profile: &Profile) -> Result<()> {
+        let (mut config, macro_keys) = self.read_settings()?;
+        for (layer, key) in macro_keys {
+            if let Ok(r#macro) = self.read_macro(layer, key) {
+                config.layer_mut(layer).macros[key as usize] = Some(r#macro);
+            }
+        }
+        profile.apply(&mut config)?;
+        self.write_config(&config)
+    }
+
+    /// Describes the pad's whole configuration as a profile.
+    pub fn dump_profile(&mut self) -> Result<Profile> {
+        Ok(Profile::from_config(&self.read_config()?))
+    }
+}
@@ -0,0 +1,368 @@
+//! Recording macros from Linux input devices.
+//!
+//! A [`Recorder`] takes the `input_event` records an evdev node such as `/dev/input/event3`
+//! reads as, or any other stream of them, and turns the key presses into a [`Macro`]. The time
+//! between presses becomes the delay of each input, rounded to the pad's 10ms steps, after
+//! scaling or clamping it if asked to.
+
+use std::{io, time::Duration};
+
+use crate::{
+    Error, KeyCode, KeyPress, Macro, MacroData, Repetition, Result, MAX_DELAY, MAX_INPUTS,
+};
+
+/// `EV_KEY`, the event type of key presses and releases
+const EV_KEY: u16 = 0x01;
+
+/// Width of the fields of the `struct timeval` starting every record
+#[cfg(target_os = "linux")]
+const LONG: usize = std::mem::size_of::<libc::timeval>() / 2;
+/// Width of the fields of the `struct timeval` starting every record, as on most Linux machines
+#[cfg(not(target_os = "linux"))]
+const LONG: usize = std::mem::size_of::<usize>();
+
+/// A `struct input_event` as the kernel lays it out on this machine.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EvdevEvent {
+    /// Time of the event, since the epoch or boot depending on the device's clock
+    pub time: Duration,
+    /// `EV_*` type
+    pub kind: u16,
+    /// `KEY_*`, `BTN_*`... code, depending on `kind`
+    pub code: u16,
+    /// For keys, 0 on release, 1 on press and 2 on autorepeat
+    pub value: i32,
+}
+
+/// Turns key events into the inputs of a macro.
+///
+/// Releases of keys pressed before recording started and autorepeats are left out, and keys still
+/// held when recording stops are released at the end of the macro.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    r#macro: Macro,
+    stop_key: Option<KeyCode>,
+    time_scale: f64,
+    /// Longest delay to record, in steps of 10ms
+    max_delay: u16,
+    /// Time of the first recorded input, and the 10ms step of the last one since then
+    start: Option<Duration>,
+    last_step: u128,
+    held: Vec<KeyCode>,
+    skipped: Vec<u16>,
+    stopped: bool,
+    full: bool,
+}
+
+impl EvdevEvent {
+    /// Size of a record, 24 bytes on 64 bit machines and x32, 16 on other 32 bit ones
+    pub const SIZE: usize = 2 * LONG + 8;
+
+    pub fn key(time: Duration, code: u16, value: i32) -> Self {
+        Self {
+            time,
+            kind: EV_KEY,
+            code,
+            value,
+        }
+    }
+
+    /// Reads a record laid out in native byte order, `bytes` holding [`EvdevEvent::SIZE`] of them.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let long = |offset: usize| {
+            let mut value = [0; 8];
+            let range = offset..offset + LONG;
+            if cfg!(target_endian = "little") {
+                value[..LONG].copy_from_slice(&bytes[range]);
+                u64::from_le_bytes(value)
+            } else {
+                value[8 - LONG..].copy_from_slice(&bytes[range]);
+                u64::from_be_bytes(value)
+            }
+        };
+        let rest = &bytes[2 * LONG..Self::SIZE];
+
+        Self {
+            time: Duration::from_secs(long(0)) + Duration::from_micros(long(LONG)),
+            kind: u16::from_ne_bytes([rest[0], rest[1]]),
+            code: u16::from_ne_bytes([rest[2], rest[3]]),
+            value: i32::from_ne_bytes([rest[4], rest[5], rest[6], rest[7]]),
+        }
+    }
+
+    /// Writes the record the way [`EvdevEvent::from_bytes`] reads it.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let long = |value: u64| {
+            if cfg!(target_endian = "little") {
+                value.to_le_bytes()[..LONG].to_vec()
+            } else {
+                value.to_be_bytes()[8 - LONG..].to_vec()
+            }
+        };
+
+        let mut bytes = long(self.time.as_secs());
+        bytes.extend(long(self.time.subsec_micros() as u64));
+        bytes.extend(self.kind.to_ne_bytes());
+        bytes.extend(self.code.to_ne_bytes());
+        bytes.extend(self.value.to_ne_bytes());
+        bytes
+    }
+}
+
+impl Recorder {
+    /// A recorder making a macro with [`Repetition::UntilNextKeyPressed`], the profile default.
+    pub fn new() -> Self {
+        Self {
+            r#macro: Macro::new(Repetition::UntilNextKeyPressed),
+            stop_key: None,
+            time_scale: 1.0,
+            max_delay: MAX_DELAY,
+            start: None,
+            last_step: 0,
+            held: Vec::new(),
+            skipped: Vec::new(),
+            stopped: false,
+            full: false,
+        }
+    }
+
+    pub fn repetition(mut self, repetition: Repetition) -> Self {
+        self.r#macro.repetition = repetition;
+        self
+    }
+
+    /// Stops recording when `key_code` is pressed, without recording it. Otherwise a live device
+    /// is recorded until the macro is full.
+    pub fn stop_key(mut self, key_code: KeyCode) -> Self {
+        self.stop_key = Some(key_code);
+        self
+    }
+
+    /// Multiplies the time between inputs by `scale`, e.g. 0.5 to play the macro twice as fast.
+    ///
+    /// Fails unless `scale` is finite and not negative.
+    pub fn time_scale(mut self, scale: f64) -> Result<Self> {
+        if !scale.is_finite() || scale < 0.0 {
+            return Err(Error::invalid_config(
+                "time scale",
+                format!("{scale} is negative or not finite"),
+            ));
+        }
+
+        self.time_scale = scale;
+        Ok(self)
+    }
+
+    /// Shortens the delays longer than `delay`, rounded down to 10ms, e.g. to leave out the pauses
+    /// taken while thinking.
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = (delay.as_millis() / 10).min(MAX_DELAY as u128) as u16;
+        self
+    }
+
+    /// Plays every input right after the one before, whatever the time between them.
+    pub fn drop_delays(self) -> Self {
+        self.max_delay(Duration::ZERO)
+    }
+
+    /// Whether the stop key was pressed or the macro is full.
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Whether recording stopped because a key press didn't fit in the macro anymore.
+    pub fn is_full(&self) -> bool {
+        self.full
+    }
+
+    /// Linux codes of the keys pressed that no [`KeyCode`] stands for, which were left out.
+    pub fn skipped(&self) -> &[u16] {
+        &self.skipped
+    }
+
+    /// The inputs recorded so far.
+    pub fn data(&self) -> &[MacroData] {
+        &self.r#macro.data
+    }
+
+    /// Records a key event, ignoring any other kind.
+    pub fn push(&mut self, event: &EvdevEvent) -> Result<()> {
+        if self.stopped || event.kind != EV_KEY {
+            return Ok(());
+        }
+
+        let key_press = match event.value {
+            0 => KeyPress::Up,
+            1 => KeyPress::Down,
+            _ => return Ok(()),
+        };
+        let Some(key_code) = KeyCode::from_evdev(event.code) else {
+            if !self.skipped.contains(&event.code) {
+                self.skipped.push(event.code);
+            }
+            return Ok(());
+        };
+
+        if key_press == KeyPress::Down && Some(key_code) == self.stop_key {
+            self.stopped = true;
+            return Ok(());
+        }
+
+        match key_press {
+            KeyPress::Down if !self.held.contains(&key_code) => self.held.push(key_code),
+            KeyPress::Up if self.held.contains(&key_code) => self.held.retain(|k| *k != key_code),
+            _ => return Ok(()),
+        }
+
+        // the releases of the keys still held have to fit too
+        if self.r#macro.data.len() + 1 + self.held.len() > MAX_INPUTS {
+            self.stopped = true;
+            self.full = true;
+            self.held.retain(|k| *k != key_code);
+            return Ok(());
+        }
+
+        // rounding the time since the first input rather than since the previous one keeps
+        // rounding errors from adding up
+        let start = *self.start.get_or_insert(event.time);
+        let elapsed = event.time.saturating_sub(start).as_secs_f64() * self.time_scale;
+        let elapsed = Duration::try_from_secs_f64(elapsed).map_err(|_| {
+            Error::invalid_config(
+                "time scale",
+                format!("{} makes the time between inputs too long", self.time_scale),
+            )
+        })?;
+        let step = (elapsed.as_millis() + 5) / 10;
+        let delay = step
+            .saturating_sub(self.last_step)
+            .min(self.max_delay as u128) as u16;
+        self.last_step = step;
+
+        self.r#macro.add_macro_data(MacroData {
+            key_press,
+            delay,
+            key_code,
+        })
+    }
+
+    /// Records `events` until they run out or recording stops.
+    pub fn record_events(&mut self, events: impl IntoIterator<Item = EvdevEvent>) -> Result<()> {
+        for event in events {
+            if self.stopped {
+                break;
+            }
+            self.push(&event)?;
+        }
+
+        Ok(())
+    }
+
+    /// Records the events read from `reader` until it ends or recording stops.
+    pub fn record(&mut self, mut reader: impl io::Read) -> Result<()> {
+        let mut record = [0; EvdevEvent::SIZE];
+
+        while !self.stopped {
+            match reader.read_exact(&mut record) {
+                Ok(()) => self.push(&EvdevEvent::from_bytes(&record))?,
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(Error::Io(err)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The recorded macro, releasing the keys still held.
+    pub fn finish(mut self) -> Macro {
+        for key_code in self.held.drain(..).rev() {
+            self.r#macro.data.push(MacroData {
+                key_press: KeyPress::Up,
+                delay: 0,
+                key_code,
+            });
+        }
+
+        self.r#macro
+    }
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod device {
+    use std::{
+        fs::File,
+        io,
+        os::fd::AsRawFd,
+        path::{Path, PathBuf},
+    };
+
+    use crate::{hidraw::ioc, Error, Result};
+
+    /// `EVIOCGRAB`, `_IOW('E', 0x90, int)`
+    const EVIOCGRAB: u32 = ioc::request(ioc::WRITE, b'E', 0x90, std::mem::size_of::<libc::c_int>());
+
+    // the records are read as the kernel writes them
+    const _: () = assert!(super::EvdevEvent::SIZE == std::mem::size_of::<libc::input_event>());
+
+    /// An open `/dev/input/event*` node, reading as a stream of [`super::EvdevEvent`] records.
+    #[derive(Debug)]
+    pub struct EvdevFile {
+        file: File,
+    }
+
+    impl EvdevFile {
+        pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+            let file = File::open(path).map_err(Error::Io)?;
+
+            Ok(Self { file })
+        }
+
+        /// Keeps the device's events from reaching anything else while the file is open, so keys
+        /// pressed while recording don't also type into the desktop.
+        pub fn grab(&self) -> Result<()> {
+            // SAFETY: EVIOCGRAB takes its argument by value
+            let result =
+                unsafe { libc::ioctl(self.file.as_raw_fd(), EVIOCGRAB as _, 1 as libc::c_int) };
+            if result < 0 {
+                return Err(Error::Io(io::Error::last_os_error()));
+            }
+
+            Ok(())
+        }
+
+        /// Paths of the `event*` nodes in `/dev/input`, in order.
+        pub fn find() -> Result<Vec<PathBuf>> {
+            let mut paths = std::fs::read_dir("/dev/input")
+                .map_err(Error::Io)?
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| name.starts_with("event"))
+                })
+                .collect::<Vec<_>>();
+            paths.sort_by_key(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name["event".len()..].parse::<u32>().ok())
+            });
+
+            Ok(paths)
+        }
+    }
+
+    impl io::Read for EvdevFile {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            io::Read::read(&mut self.file, buf)
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use device::EvdevFile;
@@ -1,99 +1,51 @@
-use rusb::UsbContext;
-
-use crate::{Brightness, Falcon8, Flow, KeyCode, LEDMode, Layer, Mode};
-
-#[repr(C)]
-#[derive(Clone, Copy)]
-pub union Report {
+use num_traits::FromPrimitive;
+
+use crate::{Brightness, Color, Falcon8, Flow, Key, KeyCode, LEDMode, Layer, Mode, Transport};
+
+const MODE: usize = 0x01;
+const ACTIVE_LAYER: usize = 0x02;
+const LED_MODE: usize = 0x85;
+const BRIGHTNESS: usize = 0x86;
+const FLOW: usize = 0x87;
+const LED_COLOR: usize = 0x88;
+
+/// A raw 264-byte feature report, as exchanged with the pad.
+///
+/// Any byte pattern is a valid `Report`; use [`Report::decode`] to get at the typed fields.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Report {
     bytes: [u8; 264],
-    data: ReportData,
 }
 
-#[repr(C)]
-#[derive(Clone, Debug, Copy)]
-pub struct ReportData {
+/// The typed view of a [`Report`], produced by [`Report::decode`].
+///
+/// Bytes the crate doesn't understand yet are kept around, so [`DecodedReport::encode`] gives
+/// back the original report when nothing was changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodedReport {
     pub zeroth_byte: u8,
     pub mode: Mode,
     pub active_layer: Layer,
 
-    // --------------------
-    // Keys
-    // --------------------
-    _s1: [u8; 5],
-    pub key_one: KeyCode,
-    pub key_five: KeyCode,
-    pub macro_key_three: u8,
-    pub macro_key_seven: u8,
-    _s2: u8,
-    pub key_two: KeyCode,
-    pub key_six: KeyCode,
-    _s3: [u8; 3],
-    pub key_three: KeyCode,
-    pub key_seven: KeyCode,
-    _s4: [u8; 3],
-    pub key_four: KeyCode,
-    pub key_eight: KeyCode,
-
-    // skip to colors
-    _s5: [u8; 33],
-
-    // --------------------
-    // Colors
-    // --------------------
-
-    // Red
-    pub key_one_red: u8,
-    pub key_two_red: u8,
-    pub key_four_red: u8,
-    _s6: [u8; 2],
-    pub key_five_red: u8,
-    pub key_three_red: u8,
-    pub key_eight_red: u8,
-    _s7: [u8; 3],
-    pub key_six_red: u8,
-    _s8: [u8; 4],
-    pub key_seven_red: u8,
-
-    _s9: [u8; 8],
-
-    // Green
-    pub key_one_green: u8,
-    pub key_two_green: u8,
-    pub key_four_green: u8,
-    _s10: [u8; 2],
-    pub key_five_green: u8,
-    pub key_three_green: u8,
-    pub key_eight_green: u8,
-    _s11: [u8; 3],
-    pub key_six_green: u8,
-    _s12: [u8; 4],
-    pub key_seven_green: u8,
-
-    _s13: [u8; 8],
-
-    // Blue
-    pub key_one_blue: u8,
-    pub key_two_blue: u8,
-    pub key_four_blue: u8,
-    _s14: [u8; 2],
-    pub key_five_blue: u8,
-    pub key_three_blue: u8,
-    pub key_eight_blue: u8,
-    _s15: [u8; 3],
-    pub key_six_blue: u8,
-    _s16: [u8; 4],
-    pub key_seven_blue: u8,
-
-    // --------------------
-    // LED Stuff
-    // --------------------
-    _s17: [u8; 8],
+    /// Key bindings, indexed by [`Key`]
+    pub key_codes: [KeyCode; 8],
+    /// Per-key colors used by [`LEDMode::Custom`], indexed by [`Key`]
+    pub key_colors: [Color; 8],
+
     pub led_mode: LEDMode,
     pub brightness: Brightness,
     pub flow: Flow,
-    pub led_red: u8,
-    pub led_green: u8,
-    pub led_blue: u8,
+    pub led_color: Color,
+
+    raw: Report,
+}
+
+/// A byte in a report that doesn't map to any known value of its field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeError {
+    pub field: &'static str,
+    pub offset: usize,
+    pub value: u8,
 }
 
 pub const DEFAULT_REPORT: [u8; 264] = [
@@ -116,110 +68,74 @@ pub const DEFAULT_REPORT: [u8; 264] = [
     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
 ];
 
-#[allow(unused)]
-pub const DEFAULT_REPORT_DATA: ReportData = ReportData {
-    zeroth_byte: 0x07,
-    mode: Mode::KeyRead,
-    active_layer: Layer::One,
-
-    key_one: KeyCode::Mute,
-    key_two: KeyCode::PlayPause,
-    key_three: KeyCode::MediaStop,
-    key_four: KeyCode::VolumeUp,
-    key_five: KeyCode::MediaLaunch,
-    key_six: KeyCode::MediaPrevious,
-    key_seven: KeyCode::MediaNext,
-    key_eight: KeyCode::VolumeDown,
-
-    key_one_red: 0,
-    key_two_red: 0,
-    key_three_red: 0,
-    key_four_red: 0,
-    key_five_red: 0,
-    key_six_red: 0,
-    key_seven_red: 0,
-    key_eight_red: 0,
-
-    key_one_blue: 0,
-    key_two_blue: 0,
-    key_three_blue: 0,
-    key_four_blue: 0,
-    key_five_blue: 0,
-    key_six_blue: 0,
-    key_seven_blue: 0,
-    key_eight_blue: 0,
-
-    key_one_green: 0,
-    key_two_green: 0,
-    key_three_green: 0,
-    key_four_green: 0,
-    key_five_green: 0,
-    key_six_green: 0,
-    key_seven_green: 0,
-    key_eight_green: 0,
-
-    macro_key_three: 0,
-    macro_key_seven: 0,
-
-    led_mode: LEDMode::Static,
-    brightness: Brightness::Max,
-    flow: Flow::Constant,
-    led_red: 0,
-    led_green: 0,
-    led_blue: 0,
-
-    _s1: [0; 5],
-    _s2: 0,
-    _s3: [0; 3],
-    _s4: [0; 3],
-    _s5: [0; 0x21],
-    _s6: [0; 2],
-    _s7: [0; 3],
-    _s8: [0; 4],
-    _s9: [0; 8],
-    _s10: [0; 2],
-    _s11: [0; 3],
-    _s12: [0; 4],
-    _s13: [0; 8],
-    _s14: [0; 2],
-    _s15: [0; 3],
-    _s16: [0; 4],
-    _s17: [0; 8],
-};
-
 impl Report {
     pub fn new() -> Self {
         Self { bytes: [0; 264] }
     }
 
-    pub fn from_falcon<T: UsbContext>(falcon: &Falcon8<T>) -> Self {
-        let mut report = Self { bytes: [0; 264] };
-        report.data_mut().zeroth_byte = 0x07;
-        report.data_mut().mode = Mode::KeyRead;
-        report.data_mut().active_layer = falcon.active_layer;
-        report
+    pub fn from_falcon<T: Transport>(falcon: &Falcon8<T>) -> Self {
+        Self::empty().clear(Some(falcon.active_layer))
     }
 
     pub fn empty() -> Self {
         Self { bytes: [0; 264] }
     }
 
+    /// The report committing everything written so far, see [`Falcon8::finalize`].
+    pub(crate) fn finalize() -> Self {
+        let mut report = Self::new();
+        report[0] = 0x07;
+        report.set_mode(Mode::Finalize);
+        report
+    }
+
     pub fn as_bytes(&self) -> &[u8; 264] {
-        unsafe { &self.bytes }
+        &self.bytes
     }
 
     pub fn as_bytes_mut(&mut self) -> &mut [u8; 264] {
-        unsafe { &mut self.bytes }
+        &mut self.bytes
     }
 
-    /// Gets the report data
-    pub fn data(&self) -> &ReportData {
-        unsafe { &self.data }
+    /// Parses every known field of the report, failing on the first byte with no known meaning.
+    pub fn decode(&self) -> Result<DecodedReport, DecodeError> {
+        let mut key_codes = [KeyCode::Disable; 8];
+        let mut key_colors = [Color::BLACK; 8];
+        for key in Key::ALL {
+            key_codes[key as usize] = self.field("key code", key.to_index(), KeyCode::from_u8)?;
+
+            let (red, green, blue) = key.to_color_indices();
+            key_colors[key as usize] = Color::new(self[red], self[green], self[blue]);
+        }
+
+        Ok(DecodedReport {
+            zeroth_byte: self[0],
+            mode: self.field("mode", MODE, Mode::from_u8)?,
+            active_layer: self.field("layer", ACTIVE_LAYER, Layer::from_u8)?,
+
+            key_codes,
+            key_colors,
+
+            led_mode: self.field("LED mode", LED_MODE, LEDMode::from_u8)?,
+            brightness: self.field("brightness", BRIGHTNESS, Brightness::from_u8)?,
+            flow: self.field("flow", FLOW, Flow::from_u8)?,
+            led_color: Color::new(self[LED_COLOR], self[LED_COLOR + 1], self[LED_COLOR + 2]),
+
+            raw: *self,
+        })
     }
 
-    /// Gets the report data mutably
-    pub fn data_mut(&mut self) -> &mut ReportData {
-        unsafe { &mut self.data }
+    fn field<V>(
+        &self,
+        field: &'static str,
+        offset: usize,
+        decode: fn(u8) -> Option<V>,
+    ) -> Result<V, DecodeError> {
+        decode(self[offset]).ok_or(DecodeError {
+            field,
+            offset,
+            value: self[offset],
+        })
     }
 
     /// Resets the report to the default state, `DEFAULT_REPORT`
@@ -230,9 +146,9 @@ impl Report {
     /// Clears the report, setting all bytes to 0 except the first 3
     pub fn clear(mut self, layer: Option<Layer>) -> Self {
         self.fill(0);
-        self.data.zeroth_byte = 0x07;
-        self.data.mode = Mode::KeyRead;
-        self.data.active_layer = layer.unwrap_or(Layer::One);
+        self[0] = 0x07;
+        self.set_mode(Mode::KeyRead)
+            .set_active_layer(layer.unwrap_or(Layer::One));
         self
     }
 
@@ -246,213 +162,179 @@ impl Report {
 
     ///  TODO: figure out what these mean, 0x07, 0x82/0x02
     pub fn first_byte(&self) -> u8 {
-        self.data().zeroth_byte
+        self[0]
     }
 
     // Second Byte
 
-    pub fn mode(&self) -> Mode {
-        self.data().mode
+    pub fn mode(&self) -> Result<Mode, DecodeError> {
+        self.field("mode", MODE, Mode::from_u8)
     }
 
     pub fn set_mode(&mut self, mode: Mode) -> &mut Self {
-        self.data_mut().mode = mode;
+        self[MODE] = mode as u8;
         self
     }
 
     // Active Layer
 
-    pub fn active_layer(&self) -> Layer {
-        self.data().active_layer
+    pub fn active_layer(&self) -> Result<Layer, DecodeError> {
+        self.field("layer", ACTIVE_LAYER, Layer::from_u8)
     }
 
     pub fn set_active_layer(&mut self, layer: Layer) -> &mut Self {
-        self.data_mut().active_layer = layer;
+        self[ACTIVE_LAYER] = layer as u8;
+        self
+    }
+
+    // LED Stuff
+
+    pub fn set_led_mode(&mut self, mode: LEDMode) -> &mut Self {
+        self[LED_MODE] = mode as u8;
+        self
+    }
+
+    pub fn set_brightness(&mut self, brightness: Brightness) -> &mut Self {
+        self[BRIGHTNESS] = brightness as u8;
         self
     }
+
+    pub fn set_flow(&mut self, flow: Flow) -> &mut Self {
+        self[FLOW] = flow as u8;
+        self
+    }
+
+    pub fn set_led_color(&mut self, color: impl Into<Color>) -> &mut Self {
+        let color = color.into();
+        self[LED_COLOR] = color.red;
+        self[LED_COLOR + 1] = color.green;
+        self[LED_COLOR + 2] = color.blue;
+        self
+    }
+}
+
+impl DecodedReport {
+    /// Writes every field back over the bytes the report was decoded from.
+    pub fn encode(&self) -> Report {
+        let mut report = self.raw;
+        report[0] = self.zeroth_byte;
+        report
+            .set_mode(self.mode)
+            .set_active_layer(self.active_layer)
+            .set_led_mode(self.led_mode)
+            .set_brightness(self.brightness)
+            .set_flow(self.flow)
+            .set_led_color(self.led_color);
+
+        for key in Key::ALL {
+            report.set_key(key, self.key_codes[key as usize]);
+            report.set_key_color(key, self.key_colors[key as usize]);
+        }
+
+        report
+    }
 }
 
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unknown {} 0x{:02X} at offset 0x{:02X}",
+            self.field, self.value, self.offset
+        )
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
 impl std::fmt::Debug for Report {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Report")
-            .field(
-                "zeroth_byte",
-                &format_args!("0x{:02X}", self.data().zeroth_byte),
-            )
-            .field(
-                "set_get_mode",
-                &format_args!("0x{:02X}", self.data().mode as u8),
-            )
+        fn typed<V: std::fmt::Debug>(byte: u8, decode: fn(u8) -> Option<V>) -> String {
+            match decode(byte) {
+                Some(value) => format!("{value:?}"),
+                None => format!("Unknown(0x{byte:02X})"),
+            }
+        }
+
+        let rgb = |(red, green, blue): (usize, usize, usize)| {
+            Color::new(self[red], self[green], self[blue])
+        };
+
+        let mut s = f.debug_struct("Report");
+        s.field("zeroth_byte", &format_args!("0x{:02X}", self[0]))
+            .field("set_get_mode", &format_args!("0x{:02X}", self[MODE]))
             .field(
                 "active_layer",
-                &format_args!("0x{:02X}", self.data().active_layer as u8),
-            )
-            .field("key_one", &self.data().key_one)
-            .field("key_two", &self.data().key_two)
-            .field("key_three", &self.data().key_three)
-            .field("key_four", &self.data().key_four)
-            .field("key_five", &self.data().key_five)
-            .field("key_six", &self.data().key_six)
-            .field("key_seven", &self.data().key_seven)
-            .field("key_eight", &self.data().key_eight)
-            .field(
-                "key_one_rgb",
-                &format_args!(
-                    "({:02X}, {:02X}, {:02X})",
-                    self.data().key_one_red,
-                    self.data().key_one_green,
-                    self.data().key_one_blue,
-                ),
-            )
-            .field(
-                "key_two_rgb",
-                &format_args!(
-                    "({:02X}, {:02X}, {:02X})",
-                    self.data().key_two_red,
-                    self.data().key_two_green,
-                    self.data().key_two_blue,
-                ),
-            )
-            .field(
-                "key_three_rgb",
-                &format_args!(
-                    "({:02X}, {:02X}, {:02X})",
-                    self.data().key_three_red,
-                    self.data().key_three_green,
-                    self.data().key_three_blue,
-                ),
-            )
-            .field(
-                "key_four_rgb",
-                &format_args!(
-                    "({:02X}, {:02X}, {:02X})",
-                    self.data().key_four_red,
-                    self.data().key_four_green,
-                    self.data().key_four_blue,
-                ),
-            )
-            .field(
-                "key_five_rgb",
-                &format_args!(
-                    "({:02X}, {:02X}, {:02X})",
-                    self.data().key_five_red,
-                    self.data().key_five_green,
-                    self.data().key_five_blue,
-                ),
-            )
-            .field(
-                "key_six_rgb",
-                &format_args!(
-                    "({:02X}, {:02X}, {:02X})",
-                    self.data().key_six_red,
-                    self.data().key_six_green,
-                    self.data().key_six_blue,
-                ),
-            )
-            .field(
-                "key_seven_rgb",
-                &format_args!(
-                    "({:02X}, {:02X}, {:02X})",
-                    self.data().key_seven_red,
-                    self.data().key_seven_green,
-                    self.data().key_seven_blue,
-                ),
-            )
-            .field(
-                "key_eight_rgb",
-                &format_args!(
-                    "({:02X}, {:02X}, {:02X})",
-                    self.data().key_eight_red,
-                    self.data().key_eight_green,
-                    self.data().key_eight_blue,
-                ),
-            )
-            .field("led_mode", &self.data().led_mode)
-            .field("brightness", &self.data().brightness)
-            .field("flow", &self.data().flow)
-            .field(
-                "global_rgb",
-                &format_args!(
-                    "({:02X}, {:02X}, {:02X})",
-                    self.data().led_red,
-                    self.data().led_green,
-                    self.data().led_blue,
-                ),
-            )
-            .finish()
+                &format_args!("0x{:02X}", self[ACTIVE_LAYER]),
+            );
+
+        for key in Key::ALL {
+            s.field(
+                key.field_name(),
+                &format_args!("{}", typed(self[key.to_index()], KeyCode::from_u8)),
+            );
+        }
+        for key in Key::ALL {
+            s.field(
+                &format!("{}_rgb", key.field_name()),
+                &format_args!("{}", rgb(key.to_color_indices())),
+            );
+        }
+
+        s.field(
+            "led_mode",
+            &format_args!("{}", typed(self[LED_MODE], LEDMode::from_u8)),
+        )
+        .field(
+            "brightness",
+            &format_args!("{}", typed(self[BRIGHTNESS], Brightness::from_u8)),
+        )
+        .field(
+            "flow",
+            &format_args!("{}", typed(self[FLOW], Flow::from_u8)),
+        )
+        .field(
+            "global_rgb",
+            &format_args!("{}", rgb((LED_COLOR, LED_COLOR + 1, LED_COLOR + 2))),
+        )
+        .finish()
     }
 }
 
 impl std::fmt::Display for Report {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.data())
+        match self.decode() {
+            Ok(decoded) => write!(f, "{decoded}"),
+            Err(err) => write!(f, "Report {{ {err} }}"),
+        }
     }
 }
 
-impl std::fmt::Display for ReportData {
+impl std::fmt::Display for DecodedReport {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "ReportData {{ zeroth_byte: 0x{:02X}, set_get_mode: 0x{:02X}, active_layer: 0x{:02X}, ",
+            "DecodedReport {{ zeroth_byte: 0x{:02X}, set_get_mode: 0x{:02X}, active_layer: 0x{:02X}, ",
             self.zeroth_byte, self.mode as u8, self.active_layer as u8,
         )?;
 
-        write!(
-            f,
-            "key_one: {}, key_two: {}, key_three: {}, key_four: {}, key_five: {}, key_six: {}, key_seven: {}, key_eight: {}, ",
-            self.key_one,
-            self.key_two,
-            self.key_three,
-            self.key_four,
-            self.key_five,
-            self.key_six,
-            self.key_seven,
-            self.key_eight,
-        )?;
-
-        write!(
-            f,
-            "key_one_rgb: ({:02X}, {:02X}, {:02X}), key_two_rgb: ({:02X}, {:02X}, {:02X}), ",
-            self.key_one_red,
-            self.key_one_green,
-            self.key_one_blue,
-            self.key_two_red,
-            self.key_two_green,
-            self.key_two_blue,
-        )?;
-
-        write!(
-            f,
-            "key_three_rgb: ({:02X}, {:02X}, {:02X}), key_four_rgb: ({:02X}, {:02X}, {:02X}), ",
-            self.key_three_red,
-            self.key_three_green,
-            self.key_three_blue,
-            self.key_four_red,
-            self.key_four_green,
-            self.key_four_blue,
-        )?;
-
-        write!(
-            f,
-            "key_five_rgb: ({:02X}, {:02X}, {:02X}), key_six_rgb: ({:02X}, {:02X}, {:02X}), ",
-            self.key_five_red,
-            self.key_five_green,
-            self.key_five_blue,
-            self.key_six_red,
-            self.key_six_green,
-            self.key_six_blue,
-        )?;
+        for key in Key::ALL {
+            write!(
+                f,
+                "{}: {}, ",
+                key.field_name(),
+                self.key_codes[key as usize]
+            )?;
+        }
 
-        write!(
-            f,
-            "key_seven_rgb: ({:02X}, {:02X}, {:02X}), key_eight_rgb: ({:02X}, {:02X}, {:02X}), ",
-            self.key_seven_red,
-            self.key_seven_green,
-            self.key_seven_blue,
-            self.key_eight_red,
-            self.key_eight_green,
-            self.key_eight_blue,
-        )?;
+        for key in Key::ALL {
+            write!(
+                f,
+                "{}_rgb: {}, ",
+                key.field_name(),
+                self.key_colors[key as usize]
+            )?;
+        }
 
         write!(
             f,
@@ -465,11 +347,7 @@ impl std::fmt::Display for ReportData {
             self.flow as u8,
         )?;
 
-        write!(
-            f,
-            "global_rgb: ({:02X}, {:02X}, {:02X}) }}",
-            self.led_red, self.led_green, self.led_blue,
-        )?;
+        write!(f, "global_rgb: {} }}", self.led_color)?;
 
         Ok(())
     }
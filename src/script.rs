@@ -0,0 +1,272 @@
+//! A small text format for macros.
+//!
+//! A script is a list of statements, separated by newlines or `;`:
+//!
+//! - `down KEY` and `up KEY` press and release a [`KeyCode`], named like [`KeyCode::from_str`]
+//!   takes it
+//! - `tap BINDING` presses and releases a key, or a [`Binding`] like `ctrl+c`
+//! - `wait DURATION` delays the next input by `30ms`, `1s`, `0.5s`..., in steps of 10ms
+//! - `repeat while-pressed` or `repeat until-next-key` sets the [`Repetition`], which is
+//!   `until-next-key` unless said otherwise
+//!
+//! A line starting with `#` is a comment. Since `;` always ends a statement, the keys it and `#`
+//! stand for are written `semicolon` and `hashtilde`.
+//!
+//! ```text
+//! # copy
+//! down ctrl; tap c; wait 30ms; up ctrl
+//! repeat while-pressed
+//! ```
+//!
+//! [`Macro`]s display as scripts, one statement per line.
+
+use std::str::FromStr;
+
+use crate::{
+    Binding, Error, KeyCode, KeyPress, Macro, MacroData, Repetition, Result, MAX_DELAY, MAX_INPUTS,
+};
+
+/// A word of a statement, with the line and column it starts at.
+#[derive(Copy, Clone)]
+struct Word<'a> {
+    text: &'a str,
+    line: usize,
+    column: usize,
+}
+
+impl Word<'_> {
+    fn error(&self, reason: impl Into<String>) -> Error {
+        Error::Syntax {
+            line: self.line,
+            column: self.column,
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Splits a script into statements, each a list of words.
+fn statements(text: &str) -> Vec<Vec<Word<'_>>> {
+    let mut statements = Vec::new();
+
+    for (line_index, line) in text.lines().enumerate() {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let mut offset = 0;
+        for statement in line.split(';') {
+            let mut words = Vec::new();
+            let mut rest = statement;
+            let mut start = offset;
+
+            while let Some(begin) = rest.find(|c: char| !c.is_whitespace()) {
+                let word = &rest[begin..];
+                let end = word.find(char::is_whitespace).unwrap_or(word.len());
+                words.push(Word {
+                    text: &word[..end],
+                    line: line_index + 1,
+                    column: line[..start + begin].chars().count() + 1,
+                });
+
+                start += begin + end;
+                rest = &word[end..];
+            }
+
+            if !words.is_empty() {
+                statements.push(words);
+            }
+            offset += statement.len() + 1;
+        }
+    }
+
+    statements
+}
+
+/// Parses `30ms`, `1s` or `0.5s` into steps of 10ms.
+fn parse_duration(word: &Word) -> Result<u32> {
+    // digits after the point a number of milliseconds can have
+    let (number, decimals) = if let Some(number) = word.text.strip_suffix("ms") {
+        (number, 0)
+    } else if let Some(number) = word.text.strip_suffix('s') {
+        (number, 3)
+    } else {
+        return Err(word.error(format!(
+            "expected a duration like 30ms, got {:?}",
+            word.text
+        )));
+    };
+
+    // read as a decimal, as floats can't hold numbers like 2.01 exactly
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    let is_digits = |text: &str| text.bytes().all(|byte| byte.is_ascii_digit());
+    if (whole.is_empty() && fraction.is_empty()) || !is_digits(whole) || !is_digits(fraction) {
+        return Err(word.error(format!("{:?} is not a duration", word.text)));
+    }
+
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > decimals {
+        return Err(word.error(format!("{:?} is not a multiple of 10ms", word.text)));
+    }
+    // only too many digits fail to parse
+    let ms = format!("0{whole}{fraction:0<decimals$}")
+        .parse::<u64>()
+        .unwrap_or(u64::MAX);
+
+    if ms % 10 != 0 {
+        return Err(word.error(format!("{ms}ms is not a multiple of 10ms")));
+    }
+
+    Ok((ms / 10).min(u32::MAX as u64) as u32)
+}
+
+/// Parses a script into its inputs and the repetition it asks for, if any.
+pub(crate) fn parse(text: &str) -> Result<(Vec<MacroData>, Option<Repetition>)> {
+    let mut data = Vec::new();
+    let mut repetition = None;
+    // delay for the next input, and the wait that asked for it
+    let mut delay = 0;
+    let mut last_wait = None;
+
+    for words in statements(text) {
+        let (command, args) = words.split_first().unwrap();
+        let arg = || match args {
+            [arg] => Ok(arg),
+            [] => Err(command.error(format!("{} takes one argument", command.text))),
+            [_, extra, ..] => Err(extra.error("unexpected argument")),
+        };
+        let key_code = |word: &Word| {
+            KeyCode::from_str(word.text)
+                .map_err(|_| word.error(format!("unknown key {:?}", word.text)))
+        };
+
+        let mut inputs = Vec::new();
+        match command.text.to_lowercase().as_str() {
+            "down" => inputs.push((KeyPress::Down, key_code(arg()?)?)),
+            "up" => inputs.push((KeyPress::Up, key_code(arg()?)?)),
+            "tap" => {
+                let arg = arg()?;
+                let binding = Binding::from_str(arg.text)
+                    .map_err(|_| arg.error(format!("unknown key {:?}", arg.text)))?;
+
+                inputs.extend(
+                    binding
+                        .modifiers
+                        .key_codes()
+                        .map(|key_code| (KeyPress::Down, key_code)),
+                );
+                if binding.key_code != KeyCode::Disable {
+                    inputs.push((KeyPress::Down, binding.key_code));
+                    inputs.push((KeyPress::Up, binding.key_code));
+                }
+                inputs.extend(
+                    binding
+                        .modifiers
+                        .key_codes()
+                        .rev()
+                        .map(|key_code| (KeyPress::Up, key_code)),
+                );
+            }
+            "wait" => {
+                let arg = arg()?;
+                delay += parse_duration(arg)?;
+                if delay > MAX_DELAY as u32 {
+                    return Err(arg.error(format!(
+                        "waits add up to more than {}ms",
+                        MAX_DELAY as u32 * 10
+                    )));
+                }
+                last_wait = Some(*command);
+            }
+            "repeat" => {
+                let arg = arg()?;
+                if repetition.is_some() {
+                    return Err(command.error("the repetition is already set"));
+                }
+                repetition = Some(match arg.text.to_lowercase().as_str() {
+                    "while-pressed" => Repetition::WhilePressed,
+                    "until-next-key" => Repetition::UntilNextKeyPressed,
+                    _ => {
+                        return Err(arg.error(format!(
+                            "expected while-pressed or until-next-key, got {:?}",
+                            arg.text
+                        )))
+                    }
+                });
+            }
+            _ => {
+                return Err(command.error(format!(
+                    "unknown statement {:?}, expected down, up, tap, wait or repeat",
+                    command.text
+                )))
+            }
+        }
+
+        for (key_press, key_code) in inputs {
+            if data.len() >= MAX_INPUTS {
+                return Err(command.error(format!("a macro can hold at most {MAX_INPUTS} inputs")));
+            }
+
+            data.push(MacroData {
+                key_press,
+                delay: delay as u16,
+                key_code,
+            });
+            delay = 0;
+            last_wait = None;
+        }
+    }
+
+    if let Some(wait) = last_wait {
+        return Err(wait.error("nothing comes after this wait"));
+    }
+
+    Ok((data, repetition))
+}
+
+impl FromStr for Macro {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (data, repetition) = parse(s)?;
+
+        Ok(Self {
+            repetition: repetition.unwrap_or(Repetition::UntilNextKeyPressed),
+            data,
+        })
+    }
+}
+
+impl std::fmt::Display for Macro {
+    /// Writes the macro as a script, folding a press and an immediate release of the same key
+    /// into a `tap`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut inputs = self.data.iter().peekable();
+
+        while let Some(data) = inputs.next() {
+            if data.delay > 0 {
+                writeln!(f, "wait {}ms", data.delay as u32 * 10)?;
+            }
+
+            let release = inputs.peek().filter(|next| {
+                data.key_press == KeyPress::Down
+                    && next.key_press == KeyPress::Up
+                    && next.key_code == data.key_code
+                    && next.delay == 0
+            });
+
+            match (release, data.key_press) {
+                (Some(_), _) => {
+                    inputs.next();
+                    writeln!(f, "tap {:?}", data.key_code)?;
+                }
+                (None, KeyPress::Down) => writeln!(f, "down {:?}", data.key_code)?,
+                (None, KeyPress::Up) => writeln!(f, "up {:?}", data.key_code)?,
+            }
+        }
+
+        match self.repetition {
+            Repetition::WhilePressed => write!(f, "repeat while-pressed"),
+            Repetition::UntilNextKeyPressed => write!(f, "repeat until-next-key"),
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use rusb::{Context, Device, UsbContext};
+
+use crate::{Error, Falcon8, Result, UsbTransport, PID, VID};
+
+/// Where a pad sits on the USB tree, and its serial number when it could be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub bus: u8,
+    pub address: u8,
+    /// Hub ports from the root hub down to the pad
+    pub port_path: Vec<u8>,
+    pub serial: Option<String>,
+}
+
+impl DeviceInfo {
+    /// Locates `device`, leaving the serial number unread.
+    pub fn of<T: UsbContext>(device: &Device<T>) -> Self {
+        Self {
+            bus: device.bus_number(),
+            address: device.address(),
+            port_path: device.port_numbers().unwrap_or_default(),
+            serial: None,
+        }
+    }
+
+    /// Whether both describe the same attachment, which the serial number plays no part in.
+    pub fn same_device(&self, other: &DeviceInfo) -> bool {
+        self.bus == other.bus && self.address == other.address
+    }
+}
+
+/// Why a pad was passed over by a [`DeviceSelector`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The pad doesn't match the named filter
+    Filtered(&'static str),
+    /// The pad can't be opened by the current user
+    Permissions,
+    /// Another program or driver holds the pad
+    Busy,
+    /// Any other USB failure while looking at the pad
+    Usb(rusb::Error),
+}
+
+/// A pad found by [`DeviceSelector::candidates`], opened at most to read its serial number.
+#[derive(Debug)]
+pub struct Candidate<T: UsbContext = Context> {
+    pub device: Device<T>,
+    pub info: DeviceInfo,
+    /// Set when the selector won't open this pad
+    pub skipped: Option<SkipReason>,
+}
+
+/// Picks a single pad out of the ones connected, by USB location or serial number.
+///
+/// ```no_run
+/// # fn main() -> falcon8::Result<()> {
+/// let falcon8 = falcon8::DeviceSelector::new().bus(3).port_path([2, 1]).open()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceSelector {
+    vid: u16,
+    pid: u16,
+    bus: Option<u8>,
+    address: Option<u8>,
+    port_path: Option<Vec<u8>>,
+    serial: Option<String>,
+}
+
+impl DeviceSelector {
+    /// Selects any Falcon-8.
+    pub fn new() -> Self {
+        Self {
+            vid: VID,
+            pid: PID,
+            bus: None,
+            address: None,
+            port_path: None,
+            serial: None,
+        }
+    }
+
+    /// Looks for another vendor and product id than the Falcon-8's.
+    pub fn usb_id(mut self, vid: u16, pid: u16) -> Self {
+        self.vid = vid;
+        self.pid = pid;
+        self
+    }
+
+    pub fn bus(mut self, bus: u8) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    pub fn address(mut self, address: u8) -> Self {
+        self.address = Some(address);
+        self
+    }
+
+    pub fn port_path(mut self, port_path: impl Into<Vec<u8>>) -> Self {
+        self.port_path = Some(port_path.into());
+        self
+    }
+
+    pub fn serial(mut self, serial: impl Into<String>) -> Self {
+        self.serial = Some(serial.into());
+        self
+    }
+
+    /// Checks `info` against every filter, returning the first one it fails.
+    pub fn matches(&self, info: &DeviceInfo) -> Result<(), SkipReason> {
+        if self.bus.is_some_and(|bus| bus != info.bus) {
+            return Err(SkipReason::Filtered("bus"));
+        }
+
+        if self.address.is_some_and(|address| address != info.address) {
+            return Err(SkipReason::Filtered("address"));
+        }
+
+        if let Some(port_path) = &self.port_path {
+            if *port_path != info.port_path {
+                return Err(SkipReason::Filtered("port path"));
+            }
+        }
+
+        if let Some(serial) = &self.serial {
+            if info.serial.as_ref() != Some(serial) {
+                return Err(SkipReason::Filtered("serial"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Why a candidate is passed over, given whether it could be opened to read its serial number.
+    pub(crate) fn skip_reason(
+        &self,
+        info: &DeviceInfo,
+        opened: std::result::Result<(), rusb::Error>,
+    ) -> Option<SkipReason> {
+        let filtered = self.matches(info).err();
+
+        match (opened, filtered) {
+            (Ok(()), filtered) => filtered,
+            // the serial filter comes last, so any other one failing means the pad is elsewhere
+            (Err(_), Some(SkipReason::Filtered(filter))) if filter != "serial" => filtered,
+            // an unread serial number only fails to match because of the open error
+            (Err(err), _) => Some(SkipReason::from(err)),
+        }
+    }
+
+    /// Lists every pad with the selector's vendor and product id, without claiming any.
+    pub fn candidates(&self) -> Result<Vec<Candidate>> {
+        self.candidates_in(&Context::new()?)
+    }
+
+    pub fn candidates_in<T: UsbContext>(&self, context: &T) -> Result<Vec<Candidate<T>>> {
+        let mut candidates = Vec::new();
+
+        for device in context.devices()?.iter() {
+            let Ok(device_desc) = device.device_descriptor() else {
+                continue;
+            };
+            if device_desc.vendor_id() != self.vid || device_desc.product_id() != self.pid {
+                continue;
+            }
+
+            let mut info = DeviceInfo::of(&device);
+
+            // the serial number can only be read through an open handle
+            let opened = device.open().map(|handle| {
+                info.serial = handle.read_serial_number_string_ascii(&device_desc).ok();
+            });
+
+            let skipped = self.skip_reason(&info, opened);
+
+            candidates.push(Candidate {
+                device,
+                info,
+                skipped,
+            });
+        }
+
+        Ok(candidates)
+    }
+
+    /// Opens and claims the one pad matching the selector.
+    pub fn open(&self) -> Result<Falcon8> {
+        self.open_in(&Context::new()?)
+    }
+
+    /// Opens and claims the one pad matching the selector, failing with
+    /// [`Error::NoMatchingDevice`] or [`Error::AmbiguousDevice`] unless exactly one does.
+    pub fn open_in<T: UsbContext>(&self, context: &T) -> Result<Falcon8<UsbTransport<T>>> {
+        let mut matches = Vec::new();
+        let mut skipped = Vec::new();
+        for candidate in self.candidates_in(context)? {
+            match candidate.skipped {
+                Some(reason) => skipped.push((candidate.info, reason)),
+                None => matches.push(candidate),
+            }
+        }
+
+        if matches.len() != 1 {
+            return Err(if matches.is_empty() {
+                Error::NoMatchingDevice { skipped }
+            } else {
+                Error::AmbiguousDevice {
+                    matches: matches
+                        .into_iter()
+                        .map(|candidate| candidate.info)
+                        .collect(),
+                }
+            });
+        }
+        let candidate = matches.remove(0);
+
+        let skip = |info: DeviceInfo, err: rusb::Error| Error::NoMatchingDevice {
+            skipped: vec![(info, SkipReason::from(err))],
+        };
+
+        let handle = match candidate.device.open() {
+            Ok(handle) => handle,
+            Err(err) => return Err(skip(candidate.info, err)),
+        };
+        handle.set_auto_detach_kernel_driver(true)?;
+
+        let mut falcon8 = Falcon8::with_transport(UsbTransport::new(candidate.device, handle));
+        match falcon8.claim_interfaces() {
+            Ok(()) => Ok(falcon8),
+            Err(Error::Usb(err)) => Err(skip(candidate.info, err)),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl Default for DeviceSelector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<rusb::Error> for SkipReason {
+    fn from(err: rusb::Error) -> Self {
+        match err {
+            rusb::Error::Access => Self::Permissions,
+            rusb::Error::Busy => Self::Busy,
+            err => Self::Usb(err),
+        }
+    }
+}
+
+impl std::fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bus {:03} address {:03}", self.bus, self.address)?;
+
+        if !self.port_path.is_empty() {
+            let ports = self.port_path.iter().map(u8::to_string).collect::<Vec<_>>();
+            write!(f, " port {}-{}", self.bus, ports.join("."))?;
+        }
+
+        if let Some(serial) = &self.serial {
+            write!(f, " serial {serial}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Filtered(filter) => write!(f, "{filter} doesn't match"),
+            Self::Permissions => write!(f, "permission denied"),
+            Self::Busy => write!(f, "in use by another program"),
+            Self::Usb(err) => write!(f, "usb error: {err}"),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use num_traits::FromPrimitive;
+
+use crate::{Error, Layer, Mode, Report, Result, Transport};
+
+/// Macro frames are keyed by layer, macro slot and frame number
+type MacroFrames = HashMap<(u8, u8, u8), Report>;
+
+/// An in-memory model of the Falcon-8 firmware, speaking the same report protocol as the pad.
+///
+/// Each layer starts out as `DEFAULT_REPORT`. [`Mode::KeyWrite`] reports update the live state
+/// of the layer they name, and [`Mode::Finalize`] commits every layer, as the pad does when it
+/// saves to flash. Every report sent is recorded so tests can assert the exact exchange.
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    layers: [Report; 5],
+    committed: [Report; 5],
+    active_layer: Layer,
+    macros: MacroFrames,
+    committed_macros: MacroFrames,
+    response: Option<Report>,
+    sent: Vec<Report>,
+    macro_frames: Vec<Report>,
+}
+
+impl Simulator {
+    pub fn new() -> Self {
+        let layers = [
+            Layer::One,
+            Layer::Two,
+            Layer::Three,
+            Layer::Four,
+            Layer::Five,
+        ]
+        .map(|layer| {
+            let mut report = Report::default();
+            report[2] = layer as u8;
+            report
+        });
+
+        Self {
+            layers,
+            committed: layers,
+            active_layer: Layer::One,
+            macros: MacroFrames::new(),
+            committed_macros: MacroFrames::new(),
+            response: None,
+            sent: Vec::new(),
+            macro_frames: Vec::new(),
+        }
+    }
+
+    /// The layer most recently selected with [`Mode::LayerWrite`].
+    pub fn active_layer(&self) -> Layer {
+        self.active_layer
+    }
+
+    /// The live configuration of `layer`, including writes that have not been finalized.
+    pub fn layer(&self, layer: Layer) -> &Report {
+        &self.layers[layer as usize - 1]
+    }
+
+    /// The configuration of `layer` as of the last [`Mode::Finalize`].
+    pub fn committed(&self, layer: Layer) -> &Report {
+        &self.committed[layer as usize - 1]
+    }
+
+    /// Every report sent to the simulator, in order.
+    pub fn sent(&self) -> &[Report] {
+        &self.sent
+    }
+
+    /// The mode of every report sent to the simulator, in order.
+    pub fn sent_modes(&self) -> Vec<Mode> {
+        self.sent
+            .iter()
+            .filter_map(|report| Mode::from_u8(report[1]))
+            .collect()
+    }
+
+    /// Every [`Mode::MacroWrite`] frame received, in order.
+    pub fn macro_frames(&self) -> &[Report] {
+        &self.macro_frames
+    }
+
+    /// Forgets the reports and macro frames recorded so far, leaving the configuration intact.
+    pub fn clear_log(&mut self) {
+        self.sent.clear();
+        self.macro_frames.clear();
+    }
+
+    /// Throws away everything that was not finalized, as unplugging the pad would.
+    pub fn power_cycle(&mut self) {
+        self.layers = self.committed;
+        self.macros = self.committed_macros.clone();
+        self.active_layer = Layer::One;
+        self.response = None;
+    }
+
+    fn report_layer(report: &Report) -> Result<Layer> {
+        Layer::from_u8(report[2]).ok_or(Error::Usb(rusb::Error::Pipe))
+    }
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transport for Simulator {
+    fn set_report(&mut self, report: &Report) -> Result<()> {
+        self.sent.push(*report);
+
+        match Mode::from_u8(report[1]).ok_or(rusb::Error::Pipe)? {
+            Mode::KeyRead => {
+                let layer = Self::report_layer(report)?;
+                self.response = Some(self.layers[layer as usize - 1]);
+            }
+            Mode::KeyWrite => {
+                let layer = Self::report_layer(report)?;
+                let stored = &mut self.layers[layer as usize - 1];
+                stored[3..].copy_from_slice(&report[3..]);
+            }
+            Mode::LayerWrite => {
+                self.active_layer = Self::report_layer(report)?;
+            }
+            Mode::MacroWrite => {
+                Self::report_layer(report)?;
+                self.macros
+                    .insert((report[2], report[3], report[4]), *report);
+                self.macro_frames.push(*report);
+            }
+            Mode::MacroRead => {
+                Self::report_layer(report)?;
+                // slots that were never written read back as an empty macro
+                let mut empty = *report;
+                empty.set_mode(Mode::MacroWrite)[8..].fill(0);
+                let frame = self.macros.get(&(report[2], report[3], report[4]));
+                self.response = Some(frame.copied().unwrap_or(empty));
+            }
+            Mode::Finalize => {
+                self.committed = self.layers;
+                self.committed_macros = self.macros.clone();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_report(&mut self, report: &mut Report) -> Result<()> {
+        // the pad stalls a read that wasn't preceded by a request
+        *report = self.response.take().ok_or(rusb::Error::Pipe)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use rusb::{Device, DeviceHandle, Direction, Recipient, RequestType, UsbContext};
+
+use crate::{Error, Report, Result};
+
+/// A channel capable of exchanging 264-byte feature reports with a Falcon-8.
+///
+/// [`crate::Falcon8`] only ever talks to the pad through this trait, so any backend (libusb, a
+/// simulator, ...) can be plugged in.
+pub trait Transport {
+    /// Sends a feature report to the device.
+    fn set_report(&mut self, report: &Report) -> Result<()>;
+
+    /// Receives a feature report from the device into `report`.
+    fn get_report(&mut self, report: &mut Report) -> Result<()>;
+
+    /// Time the device needs to process a report before it can take the next one.
+    fn report_delay(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn set_report(&mut self, report: &Report) -> Result<()> {
+        (**self).set_report(report)
+    }
+
+    fn get_report(&mut self, report: &mut Report) -> Result<()> {
+        (**self).get_report(report)
+    }
+
+    fn report_delay(&self) -> Duration {
+        (**self).report_delay()
+    }
+}
+
+/// Time a Falcon-8 needs between two reports
+pub const REPORT_DELAY: Duration = Duration::from_millis(50);
+
+/// The libusb backend, sending reports as HID class control transfers.
+#[derive(Debug)]
+pub struct UsbTransport<T: UsbContext> {
+    pub device: Device<T>,
+    pub handle: DeviceHandle<T>,
+    pub interfaces: Vec<u8>,
+}
+
+impl<T: UsbContext> UsbTransport<T> {
+    pub fn new(device: Device<T>, handle: DeviceHandle<T>) -> Self {
+        Self {
+            device,
+            handle,
+            interfaces: Vec::new(),
+        }
+    }
+
+    pub fn claim_interfaces(&mut self) -> Result<()> {
+        let config_desc = self.device.config_descriptor(0)?;
+        for interface in config_desc.interfaces() {
+            for interface_desc in interface.descriptors() {
+                for endpoint_desc in interface_desc.endpoint_descriptors() {
+                    if endpoint_desc.direction() == Direction::In {
+                        self.handle
+                            .claim_interface(interface_desc.interface_number())?;
+                        self.interfaces.push(interface_desc.interface_number());
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: UsbContext> Transport for UsbTransport<T> {
+    fn set_report(&mut self, report: &Report) -> Result<()> {
+        self.claim_interfaces()?;
+
+        let size = self.handle.write_control(
+            rusb::request_type(Direction::Out, RequestType::Class, Recipient::Interface),
+            0x09,
+            0x0307,
+            0x0002,
+            report.as_ref(),
+            Duration::from_secs(1),
+        )?;
+        if size != report.len() {
+            return Err(Error::ShortTransfer {
+                expected: report.len(),
+                actual: size,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn get_report(&mut self, report: &mut Report) -> Result<()> {
+        self.claim_interfaces()?;
+
+        let size = self.handle.read_control(
+            rusb::request_type(Direction::In, RequestType::Class, Recipient::Interface),
+            0x01,
+            0x0307,
+            0x0002,
+            report.as_mut(),
+            Duration::from_secs(1),
+        )?;
+        if size != report.len() {
+            return Err(Error::ShortTransfer {
+                expected: report.len(),
+                actual: size,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn report_delay(&self) -> Duration {
+        REPORT_DELAY
+    }
+}